@@ -0,0 +1,299 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gtk::{Align, Button, DrawingArea, Entry, GestureClick, GestureDrag, Label, Overlay, Popover, TextView};
+use gtk::gdk;
+use gtk::gdk::{ModifierType, RGBA};
+use gtk::glib;
+use gtk::glib::clone;
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::prelude::{BoxExt, ButtonExt, Cast, DrawingAreaExt, DrawingAreaExtManual, EditableExt, EventControllerExt, GestureDragExt, GestureExt, GestureSingleExt, IsA, PopoverExt, TextBufferExt, TextViewExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::{FONT_SIZES, gtk_wrappers};
+use crate::files::{AnnotationKind, MemoAnnotation};
+use crate::gtk_wrappers::{alert, drag_point_on_desktop, get_desktop, get_visible_area};
+use crate::metafolder::LabelStyle;
+use crate::zones::can_move;
+
+// room around an arrow or a line, so that its ends and the arrow head are not clipped
+const LINE_PADDING: f64 = 10f64;
+const ARROW_HEAD: f64 = 12f64;
+const NOTE_COLOR: &str = "rgba(255, 236, 120, 0.95)";
+// notes are light, their text stays dark whatever the font color of the folder
+const NOTE_TEXT_COLOR: &str = "#333333";
+
+pub(crate) fn make_annotations_menu() -> Popover {
+    let container = gtk::Box::builder().orientation(Vertical).spacing(5).build();
+    let popover = Popover::builder().child(&container).build();
+    let kinds = [(AnnotationKind::Text, "text"), (AnnotationKind::Heading, "heading"), (AnnotationKind::Note, "sticky note"),
+        (AnnotationKind::Arrow, "arrow"), (AnnotationKind::Line, "line")];
+    for (kind, label) in kinds {
+        let button = Button::builder().label(label).build();
+        button.connect_clicked(clone!(@weak popover => move |b| {
+            popover.popdown();
+            add_annotation(b, kind);
+        }));
+        container.append(&button);
+    }
+    container.append(&Label::new(Some("right click to edit, ctrl+drag to change the end of a line")));
+    popover
+}
+
+// where the widget of an annotation goes, unzoomed: lines and arrows may point left or up from their start
+pub(crate) fn widget_origin(annotation: &MemoAnnotation) -> (f64, f64) {
+    match annotation.kind {
+        AnnotationKind::Arrow | AnnotationKind::Line => (
+            annotation.x.min(annotation.x + annotation.width) as f64 - LINE_PADDING,
+            annotation.y.min(annotation.y + annotation.height) as f64 - LINE_PADDING,
+        ),
+        _ => (annotation.x as f64, annotation.y as f64),
+    }
+}
+
+pub(crate) fn make_annotation_widget(annotation: &MemoAnnotation) -> gtk::Widget {
+    let widget: gtk::Widget = match annotation.kind {
+        AnnotationKind::Text | AnnotationKind::Heading => Label::builder().use_markup(true).build().upcast(),
+        AnnotationKind::Note => {
+            let background = DrawingArea::new();
+            background.set_draw_func(|_, cr, width, height| {
+                let rgba = RGBA::parse(NOTE_COLOR).unwrap();
+                cr.set_source_rgba(rgba.red() as f64, rgba.green() as f64, rgba.blue() as f64, rgba.alpha() as f64);
+                cr.rectangle(0f64, 0f64, width as f64, height as f64);
+                let _ = cr.fill();
+            });
+            let note = Overlay::builder().child(&background).build();
+            let label = Label::builder().use_markup(true).wrap(true).halign(Align::Start).valign(Align::Start)
+                .margin_start(8).margin_end(8).margin_top(8).margin_bottom(8).build();
+            note.add_overlay(&label);
+            note.upcast()
+        }
+        AnnotationKind::Arrow | AnnotationKind::Line => DrawingArea::new().upcast(),
+    };
+    widget.add_controller(make_move_gesture(&widget));
+    let menu_gesture = GestureClick::new();
+    menu_gesture.set_button(gdk::BUTTON_SECONDARY);
+    menu_gesture.connect_pressed(clone!(@weak widget => move |g, _, _, _| {
+        g.set_state(gtk::EventSequenceState::Claimed);
+        show_annotation_menu(&widget);
+    }));
+    widget.add_controller(menu_gesture);
+    widget
+}
+
+fn markup(text: &str, size: &str, bold: bool, color: &str) -> String {
+    let weight = if bold { "bold" } else { "normal" };
+    format!("<span font_size=\"{}\" font_weight=\"{}\" color=\"{}\">{}</span>", size, weight, color, glib::markup_escape_text(text))
+}
+
+// applies the folder font settings and the annotation content to its widget
+pub(crate) fn style_annotation(widget: &gtk::Widget, annotation: &MemoAnnotation, style: &LabelStyle) {
+    match annotation.kind {
+        AnnotationKind::Text => {
+            let label = widget.downcast_ref::<Label>().unwrap();
+            label.set_label(markup(annotation.text.as_str(), style.size.as_str(), style.bold, style.color.as_str()).as_str());
+        }
+        AnnotationKind::Heading => {
+            // two sizes larger than the folder font, always bold
+            let index = FONT_SIZES.iter().position(|size| *size == style.size).unwrap_or(2);
+            let size = FONT_SIZES[(index + 2).min(FONT_SIZES.len() - 1)];
+            let label = widget.downcast_ref::<Label>().unwrap();
+            label.set_label(markup(annotation.text.as_str(), size, true, style.color.as_str()).as_str());
+        }
+        AnnotationKind::Note => {
+            let note = widget.downcast_ref::<Overlay>().unwrap();
+            note.set_size_request(annotation.width, annotation.height);
+            let label = note.child().unwrap().next_sibling().unwrap().downcast::<Label>().unwrap();
+            label.set_label(markup(annotation.text.as_str(), style.size.as_str(), style.bold, NOTE_TEXT_COLOR).as_str());
+        }
+        AnnotationKind::Arrow | AnnotationKind::Line => {
+            let area = widget.downcast_ref::<DrawingArea>().unwrap();
+            let (dx, dy) = (annotation.width as f64, annotation.height as f64);
+            area.set_content_width((dx.abs() + 2f64 * LINE_PADDING) as i32);
+            area.set_content_height((dy.abs() + 2f64 * LINE_PADDING) as i32);
+            let rgba = RGBA::parse(style.color.as_str()).unwrap_or(RGBA::WHITE);
+            let arrow = annotation.kind == AnnotationKind::Arrow;
+            area.set_draw_func(move |_, cr, _, _| {
+                let (start_x, start_y) = (LINE_PADDING - dx.min(0f64), LINE_PADDING - dy.min(0f64));
+                let (end_x, end_y) = (start_x + dx, start_y + dy);
+                cr.set_source_rgba(rgba.red() as f64, rgba.green() as f64, rgba.blue() as f64, rgba.alpha() as f64);
+                cr.set_line_width(3f64);
+                cr.move_to(start_x, start_y);
+                cr.line_to(end_x, end_y);
+                let _ = cr.stroke();
+                if arrow && (dx != 0f64 || dy != 0f64) {
+                    let angle = dy.atan2(dx);
+                    for side in [-0.5f64, 0.5f64] {
+                        cr.move_to(end_x, end_y);
+                        cr.line_to(end_x - ARROW_HEAD * (angle + side).cos(), end_y - ARROW_HEAD * (angle + side).sin());
+                    }
+                    let _ = cr.stroke();
+                }
+            });
+            area.queue_draw();
+        }
+    }
+}
+
+// dragging moves an annotation; with ctrl held it moves the end of an arrow or a line instead
+fn make_move_gesture(widget: &gtk::Widget) -> GestureDrag {
+    // pointer, and view position of the start and of the end of the annotation, when the drag began
+    let start = Rc::new(Cell::new(((0f64, 0f64), (0f64, 0f64), (0f64, 0f64))));
+    let gesture = GestureDrag::new();
+    gesture.connect_drag_begin(clone!(@strong start, @weak widget => move |g, _, _| {
+        if !can_move(&widget) {
+            g.set_state(gtk::EventSequenceState::Denied);
+            return;
+        }
+        g.set_state(gtk::EventSequenceState::Claimed);
+        let ds = gtk_wrappers::get_application(&widget);
+        let mf = ds.imp().metafolder.borrow();
+        let Some(index) = mf.annotation_index(&widget) else { return };
+        let a = &mf.annotations[index];
+        start.set((drag_point_on_desktop(g, 0f64, 0f64), mf.model_to_view(a.x as f64, a.y as f64), mf.model_to_view((a.x + a.width) as f64, (a.y + a.height) as f64)));
+    }));
+    gesture.connect_drag_update(clone!(@strong start, @weak widget => move |g, offset_x, offset_y| {
+        let ds = gtk_wrappers::get_application(&widget);
+        let mut mf = ds.imp().metafolder.borrow_mut();
+        let Some(index) = mf.annotation_index(&widget) else { return };
+        let ((pointer_x, pointer_y), (start_x, start_y), (end_x, end_y)) = start.get();
+        let (x, y) = drag_point_on_desktop(g, offset_x, offset_y);
+        let (offset_x, offset_y) = (x - pointer_x, y - pointer_y);
+        let is_line = matches!(mf.annotations[index].kind, AnnotationKind::Arrow | AnnotationKind::Line);
+        if is_line && g.current_event_state().contains(ModifierType::CONTROL_MASK) {
+            let (model_x, model_y) = mf.view_to_model(end_x + offset_x, end_y + offset_y);
+            let a = &mut mf.annotations[index];
+            a.width = model_x.round() as i32 - a.x;
+            a.height = model_y.round() as i32 - a.y;
+        } else {
+            let (model_x, model_y) = mf.view_to_model((start_x + offset_x).max(0f64), (start_y + offset_y).max(0f64));
+            let a = &mut mf.annotations[index];
+            a.x = model_x.round() as i32;
+            a.y = model_y.round() as i32;
+        }
+        style_annotation(&widget, &mf.annotations[index], &mf.label_style.borrow());
+        mf.show_annotations(&get_desktop(&widget));
+    }));
+    gesture.connect_drag_end(clone!(@weak widget => move |_, _, _| {
+        let ds = gtk_wrappers::get_application(&widget);
+        let result = ds.imp().metafolder.borrow_mut().save_annotations();
+        if let Some(err) = result {
+            alert(&widget, "folder settings could not be saved".to_string(), err.to_string());
+        }
+    }));
+    gesture
+}
+
+// adds an annotation in the middle of the part of the desktop in view; text annotations open their menu right away
+fn add_annotation(w: &impl IsA<gtk::Widget>, kind: AnnotationKind) {
+    let visible = get_visible_area(w);
+    let desktop = get_desktop(w);
+    let ds = gtk_wrappers::get_application(w);
+    let mut mf = ds.imp().metafolder.borrow_mut();
+    let (x, y) = mf.view_to_model(visible.x + visible.w / 2f64, visible.y + visible.h / 2f64);
+    let (text, width, height) = match kind {
+        AnnotationKind::Text => ("text", 0, 0),
+        AnnotationKind::Heading => ("heading", 0, 0),
+        AnnotationKind::Note => ("note", 160, 120),
+        AnnotationKind::Arrow | AnnotationKind::Line => ("", 150, 0),
+    };
+    let annotation = MemoAnnotation {
+        kind,
+        text: text.to_string(),
+        x: x.round() as i32,
+        y: y.round() as i32,
+        width,
+        height,
+    };
+    let widget = make_annotation_widget(&annotation);
+    mf.add_annotation(&desktop, annotation, widget.clone());
+    let result = mf.save_annotations();
+    drop(mf);
+    if let Some(err) = result {
+        alert(w, "folder settings could not be saved".to_string(), err.to_string());
+    }
+    if !text.is_empty() {
+        show_annotation_menu(&widget);
+    }
+}
+
+fn show_annotation_menu(widget: &gtk::Widget) {
+    let ds = gtk_wrappers::get_application(widget);
+    let mf = ds.imp().metafolder.borrow();
+    let Some(index) = mf.annotation_index(widget) else { return };
+    let annotation = mf.annotations[index].clone();
+    drop(mf);
+
+    let container = gtk::Box::builder().orientation(Vertical).spacing(10).build();
+    let popover = Popover::builder().child(&container).build();
+    let buttons = gtk::Box::builder().orientation(Horizontal).spacing(5).build();
+
+    // how to read the edited text back, for the annotations that have one
+    let text_source: Option<Box<dyn Fn() -> String>> = match annotation.kind {
+        AnnotationKind::Text | AnnotationKind::Heading => {
+            let entry = Entry::builder().text(annotation.text.as_str()).build();
+            container.append(&entry);
+            Some(Box::new(move || entry.text().to_string()))
+        }
+        AnnotationKind::Note => {
+            let text_view = TextView::builder().wrap_mode(gtk::WrapMode::WordChar).width_request(200).height_request(100).build();
+            text_view.buffer().set_text(annotation.text.as_str());
+            container.append(&text_view);
+            Some(Box::new(move || {
+                let buffer = text_view.buffer();
+                buffer.text(&buffer.start_iter(), &buffer.end_iter(), false).to_string()
+            }))
+        }
+        AnnotationKind::Arrow | AnnotationKind::Line => None,
+    };
+    if let Some(text_source) = text_source {
+        let apply = Button::builder().label("apply").build();
+        apply.connect_clicked(clone!(@weak widget, @weak popover => move |_| {
+            let ds = gtk_wrappers::get_application(&widget);
+            let mut mf = ds.imp().metafolder.borrow_mut();
+            let Some(index) = mf.annotation_index(&widget) else { return };
+            mf.annotations[index].text = text_source();
+            style_annotation(&widget, &mf.annotations[index], &mf.label_style.borrow());
+            let result = mf.save_annotations();
+            drop(mf);
+            if let Some(err) = result {
+                alert(&widget, "folder settings could not be saved".to_string(), err.to_string());
+            }
+            popover.popdown();
+        }));
+        buttons.append(&apply);
+    }
+    let delete = Button::builder().label("delete").build();
+    delete.connect_clicked(clone!(@weak widget, @weak popover => move |_| {
+        let ds = gtk_wrappers::get_application(&widget);
+        let desktop = get_desktop(&widget);
+        let mut mf = ds.imp().metafolder.borrow_mut();
+        let Some(index) = mf.annotation_index(&widget) else { return };
+        popover.popdown();
+        let result = mf.delete_annotation(&desktop, index);
+        drop(mf);
+        if let Some(err) = result {
+            alert(&desktop, "folder settings could not be saved".to_string(), err.to_string());
+        }
+    }));
+    buttons.append(&delete);
+    container.append(&buttons);
+
+    // parented to the desktop, as the menus of cells
+    let desktop = get_desktop(widget);
+    popover.set_parent(&desktop);
+    let b = widget.compute_bounds(&desktop).expect("Fatal: cannot get annotation bounds");
+    popover.set_pointing_to(Some(&gdk::Rectangle::new(b.x() as i32, b.y() as i32, b.width() as i32, b.height() as i32)));
+    popover.connect_closed(|p| {
+        glib::idle_add_local_once(clone!(@weak p => move || p.unparent()));
+    });
+    popover.popup();
+}
+
+pub(crate) fn draw_annotations(desktop: &gtk::Fixed, annotations: &[MemoAnnotation]) {
+    let ds = gtk_wrappers::get_application(desktop);
+    let mut mf = ds.imp().metafolder.borrow_mut();
+    for annotation in annotations {
+        mf.add_annotation(desktop, annotation.clone(), make_annotation_widget(annotation));
+    }
+}
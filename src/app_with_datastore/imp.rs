@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 use gtk::{glib, Label, subclass::prelude::*};
 use gtk::gio::FileMonitor;
@@ -21,6 +21,23 @@ pub struct AppWithDatastore {
     pub(crate) text_size_scale: RefCell<Option<gtk::Scale>>,
     pub(crate) font_bold_switch: RefCell<Option<gtk::Switch>>,
     pub(crate) cell_size_scale: RefCell<Option<gtk::Scale>>,
+    pub(crate) spacing_scale: RefCell<Option<gtk::Scale>>,
+    pub(crate) drop_policy_dropdown: RefCell<Option<gtk::DropDown>>,
+    pub(crate) placement_dropdown: RefCell<Option<gtk::DropDown>>,
+    pub(crate) arrivals_button: RefCell<Option<gtk::ToggleButton>>,
+    pub(crate) lock_button: RefCell<Option<gtk::ToggleButton>>,
+    pub(crate) lock_indicator: RefCell<Option<gtk::Image>>,
+    pub(crate) show_hidden_button: RefCell<Option<gtk::ToggleButton>>,
+    pub(crate) tap_button: RefCell<Option<gtk::Button>>,
+    pub(crate) zoom_commit_button: RefCell<Option<gtk::Button>>,
+    pub(crate) visual_zoom_switch: RefCell<Option<gtk::Switch>>,
+    pub(crate) auto_fit_switch: RefCell<Option<gtk::Switch>>,
+    pub(crate) arrivals_revealer: RefCell<Option<gtk::Revealer>>,
+    pub(crate) arrivals_list: RefCell<Option<gtk::ListBox>>,
+    pub(crate) minimap: RefCell<Option<gtk::DrawingArea>>,
+    pub(crate) minimap_button: RefCell<Option<gtk::ToggleButton>>,
+    // space held down, dragging then pans the desktop
+    pub(crate) space_pressed: Cell<bool>,
 }
 
 #[glib::object_subclass]
@@ -0,0 +1,90 @@
+use gtk::{Align, Button, Label, ListBox, Revealer, RevealerTransitionType, ScrolledWindow, WidgetPaintable};
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::prelude::{BoxExt, ButtonExt, IsA, ToggleButtonExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::{cell, gtk_wrappers};
+use crate::gtk_wrappers::{alert, get_desktop};
+
+const THUMBNAIL_SIZE: i32 = 32;
+
+// side tray listing the cells of files that appeared while the folder was open and have not been placed by the user yet
+pub(crate) fn make_arrivals_tray() -> (Revealer, ListBox) {
+    let container = gtk::Box::builder().orientation(Vertical).spacing(5).width_request(220).build();
+
+    let title = Label::builder().use_markup(true).label("<span font_weight=\"bold\">arrivals</span>").build();
+    container.append(&title);
+
+    let actions = gtk::Box::builder().orientation(Horizontal).spacing(5).halign(Align::Center).build();
+    let accept = Button::builder().label("accept all").build();
+    accept.set_tooltip_text(Some("keep all new icons where they are"));
+    accept.connect_clicked(|b| {
+        let ds = gtk_wrappers::get_application(b);
+        let result = ds.imp().metafolder.borrow_mut().accept_added_cells();
+        if let Some(err) = result {
+            alert(b, "folder settings could not be saved".to_string(), err.to_string());
+        }
+        refresh_arrivals(b);
+    });
+    actions.append(&accept);
+    let clear = Button::builder().label("clear highlight").build();
+    clear.set_tooltip_text(Some("stop highlighting new icons"));
+    clear.connect_clicked(|b| {
+        let ds = gtk_wrappers::get_application(b);
+        ds.imp().metafolder.borrow().clear_added_highlight();
+    });
+    actions.append(&clear);
+    container.append(&actions);
+
+    let list = ListBox::builder().build();
+    list.set_placeholder(Some(&Label::new(Some("no new files"))));
+    let scrolled_list = ScrolledWindow::builder().vexpand(true).child(&list).build();
+    container.append(&scrolled_list);
+
+    let revealer = Revealer::builder().transition_type(RevealerTransitionType::SlideLeft).child(&container).build();
+    (revealer, list)
+}
+
+pub(crate) fn refresh_arrivals(w: &impl IsA<gtk::Widget>) {
+    let ds = gtk_wrappers::get_application(w);
+    let binding = ds.imp().arrivals_list.borrow();
+    let Some(list) = binding.as_ref() else { return };
+    list.remove_all();
+
+    let mf = ds.imp().metafolder.borrow();
+    let desktop = get_desktop(w);
+    let names = mf.sorted_added_cells();
+    for name in &names {
+        let cell = mf.get_cell(name.clone());
+        let cell_image = cell.first_child().unwrap();
+        let thumbnail = gtk::Image::from_paintable(Some(&WidgetPaintable::new(Some(&cell_image))));
+        thumbnail.set_pixel_size(THUMBNAIL_SIZE);
+
+        let row = gtk::Box::builder().orientation(Horizontal).spacing(10).build();
+        row.set_tooltip_text(Some(name.as_str()));
+        row.append(&thumbnail);
+        row.append(&Label::builder().label(name.as_str()).ellipsize(gtk::pango::EllipsizeMode::Middle).build());
+        // dragging a row drops the corresponding cell on the desktop, just like dragging the cell itself
+        row.add_controller(cell::make_drag_source(name.clone(), cell, &desktop, false));
+        list.append(&row);
+    }
+
+    let button_binding = ds.imp().arrivals_button.borrow();
+    if let Some(button) = button_binding.as_ref() {
+        button.set_label(format!("arrivals ({})", names.len()).as_str());
+    }
+}
+
+pub(crate) fn reveal_arrivals(w: &impl IsA<gtk::Widget>, reveal: bool) {
+    let ds = gtk_wrappers::get_application(w);
+    let binding = ds.imp().arrivals_revealer.borrow();
+    if let Some(revealer) = binding.as_ref() {
+        revealer.set_reveal_child(reveal);
+    }
+}
+
+pub(crate) fn arrivals_toggle(w: &impl IsA<gtk::Widget>) -> bool {
+    let ds = gtk_wrappers::get_application(w);
+    let binding = ds.imp().arrivals_button.borrow();
+    binding.as_ref().map(|b| b.is_active()).unwrap_or(false)
+}
@@ -0,0 +1,198 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+use gtk::{ApplicationWindow, Button, DropTarget, EventControllerKey, Fixed, GestureDrag, Popover, PropagationPhase, ScrolledWindow};
+use gtk::gdk;
+use gtk::glib;
+use gtk::glib::{clone, ControlFlow, Propagation, SourceId};
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::prelude::{AdjustmentExt, BoxExt, ButtonExt, EventControllerExt, GestureDragExt, GestureExt, GestureSingleExt, IsA, ObjectExt, PopoverExt, RootExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::{DRAG_ACTION, gtk_wrappers, zoom};
+use crate::gtk_wrappers::{get_desktop, get_scrolled_window, is_visual_zoom_enabled};
+
+// distance from the window edge at which a drag starts scrolling
+const AUTO_SCROLL_EDGE: f64 = 40f64;
+const AUTO_SCROLL_STEP: f64 = 20f64;
+const AUTO_SCROLL_INTERVAL: Duration = Duration::from_millis(30);
+
+pub(crate) fn make_canvas_menu() -> Popover {
+    let container = gtk::Box::builder().orientation(Vertical).spacing(10).build();
+    let popover = Popover::builder().child(&container).build();
+
+    let show_everything = Button::builder().label("show everything").build();
+    show_everything.set_tooltip_text(Some("bring all icons into view - not saved"));
+    show_everything.connect_clicked(clone!(@weak popover => move |b| {
+        show_all_cells(b);
+        popover.popdown();
+    }));
+    container.append(&show_everything);
+
+    let extend_box = gtk::Box::builder().orientation(Horizontal).spacing(5).build();
+    // left, top, right, bottom in pages
+    let directions = [("go-previous-symbolic", "extend left", (1f64, 0f64, 0f64, 0f64)),
+        ("go-up-symbolic", "extend up", (0f64, 1f64, 0f64, 0f64)),
+        ("go-next-symbolic", "extend right", (0f64, 0f64, 1f64, 0f64)),
+        ("go-down-symbolic", "extend down", (0f64, 0f64, 0f64, 1f64))];
+    for (icon_name, tooltip, pages) in directions {
+        let button = Button::from_icon_name(icon_name);
+        button.set_tooltip_text(Some(tooltip));
+        button.connect_clicked(move |b| {
+            extend_by_pages(b, pages);
+        });
+        extend_box.append(&button);
+    }
+    container.append(&extend_box);
+    popover
+}
+
+// adds one visible page of room per requested direction
+fn extend_by_pages(w: &impl IsA<gtk::Widget>, (left, top, right, bottom): (f64, f64, f64, f64)) {
+    let scrolled_window = get_scrolled_window(w);
+    let h = scrolled_window.hadjustment();
+    let v = scrolled_window.vadjustment();
+    let (page_w, page_h) = (h.page_size(), v.page_size());
+    let ds = gtk_wrappers::get_application(w);
+    ds.imp().metafolder.borrow().extend_canvas(&get_desktop(w), left * page_w, top * page_h, right * page_w, bottom * page_h);
+    // scroll to the new room, once the desktop has been measured again
+    let new_h = if left > 0f64 { 0f64 } else if right > 0f64 { f64::MAX } else { h.value() };
+    let new_v = if top > 0f64 { 0f64 } else if bottom > 0f64 { f64::MAX } else { v.value() };
+    glib::idle_add_local_once(clone!(@weak h, @weak v => move || {
+        h.set_value(new_h.min(h.upper() - h.page_size()));
+        v.set_value(new_v.min(v.upper() - v.page_size()));
+    }));
+}
+
+// scales the view (or zooms, when visual zoom is off) so that all cells are visible, and scrolls to the top left
+pub(crate) fn show_all_cells(w: &impl IsA<gtk::Widget>) {
+    let scrolled_window = get_scrolled_window(w);
+    let desktop = get_desktop(w);
+    let ds = gtk_wrappers::get_application(w);
+    if is_visual_zoom_enabled(w) {
+        let mut mf = ds.imp().metafolder.borrow_mut();
+        let (right, bottom) = mf.spatial_index.borrow().extent();
+        let (width, height) = (scrolled_window.width() as f64, scrolled_window.height() as f64);
+        if right > 0f64 && bottom > 0f64 && width > 0f64 && height > 0f64 {
+            let fit = (width / right).min(height / bottom);
+            let scale = (mf.view_scale * fit).clamp(zoom::MIN_VIEW_SCALE, mf.view_scale.max(1f64));
+            mf.set_view_scale(scale, &desktop);
+        }
+    } else {
+        ds.imp().metafolder.borrow_mut().fit_to_window(w);
+    }
+    let h = scrolled_window.hadjustment();
+    let v = scrolled_window.vadjustment();
+    glib::idle_add_local_once(clone!(@weak h, @weak v => move || {
+        h.set_value(0f64);
+        v.set_value(0f64);
+    }));
+}
+
+// scrolls while something is dragged close to the window edges; at the right and bottom end the canvas grows
+pub(crate) fn add_auto_scroll(drop_target: &DropTarget, desktop: &Fixed) {
+    // pointer in scrolled window coordinates, None while nothing is dragged over the desktop
+    let pointer: Rc<Cell<Option<(f64, f64)>>> = Rc::new(Cell::new(None));
+    drop_target.connect_motion(clone!(@strong pointer, @weak desktop => @default-return DRAG_ACTION, move |_, x, y| {
+        let scrolled_window = get_scrolled_window(&desktop);
+        pointer.set(Some((x - scrolled_window.hadjustment().value(), y - scrolled_window.vadjustment().value())));
+        DRAG_ACTION
+    }));
+    // one timer at a time, however often the drag leaves and enters again
+    let timer: Rc<RefCell<Option<SourceId>>> = Rc::new(RefCell::new(None));
+    drop_target.connect_enter(clone!(@strong pointer, @strong timer, @weak desktop => @default-return DRAG_ACTION, move |target, _, _| {
+        if timer.borrow().is_some() {
+            return DRAG_ACTION;
+        }
+        let source = glib::timeout_add_local(AUTO_SCROLL_INTERVAL, clone!(@strong pointer, @strong timer, @weak desktop, @weak target => @default-return ControlFlow::Break, move || {
+            if target.current_drop().is_none() {
+                pointer.set(None);
+            }
+            let Some((x, y)) = pointer.get() else {
+                timer.replace(None);
+                return ControlFlow::Break;
+            };
+            auto_scroll(&desktop, x, y);
+            ControlFlow::Continue
+        }));
+        timer.replace(Some(source));
+        DRAG_ACTION
+    }));
+    drop_target.connect_leave(clone!(@strong pointer => move |_| {
+        pointer.set(None);
+    }));
+}
+
+fn auto_scroll(desktop: &Fixed, x: f64, y: f64) {
+    let scrolled_window = get_scrolled_window(desktop);
+    let h = scrolled_window.hadjustment();
+    let v = scrolled_window.vadjustment();
+    let (mut grow_right, mut grow_bottom) = (0f64, 0f64);
+    if x < AUTO_SCROLL_EDGE {
+        h.set_value(h.value() - AUTO_SCROLL_STEP);
+    } else if x > h.page_size() - AUTO_SCROLL_EDGE {
+        if h.value() + h.page_size() >= h.upper() {
+            grow_right = AUTO_SCROLL_STEP;
+        }
+        h.set_value(h.value() + AUTO_SCROLL_STEP);
+    }
+    if y < AUTO_SCROLL_EDGE {
+        v.set_value(v.value() - AUTO_SCROLL_STEP);
+    } else if y > v.page_size() - AUTO_SCROLL_EDGE {
+        if v.value() + v.page_size() >= v.upper() {
+            grow_bottom = AUTO_SCROLL_STEP;
+        }
+        v.set_value(v.value() + AUTO_SCROLL_STEP);
+    }
+    if grow_right > 0f64 || grow_bottom > 0f64 {
+        let ds = gtk_wrappers::get_application(desktop);
+        ds.imp().metafolder.borrow().extend_canvas(desktop, 0f64, 0f64, grow_right, grow_bottom);
+    }
+}
+
+// dragging with the middle button, or with any button while space is held, pans the desktop
+pub(crate) fn add_panning(scrolled_window: &ScrolledWindow) {
+    let start = Rc::new(Cell::new((0f64, 0f64)));
+    let pan = GestureDrag::builder().button(0).propagation_phase(PropagationPhase::Capture).build();
+    pan.connect_drag_begin(clone!(@strong start, @weak scrolled_window => move |gesture, _, _| {
+        let ds = gtk_wrappers::get_application(&scrolled_window);
+        if gesture.current_button() != gdk::BUTTON_MIDDLE && !ds.imp().space_pressed.get() {
+            gesture.set_state(gtk::EventSequenceState::Denied);
+            return;
+        }
+        gesture.set_state(gtk::EventSequenceState::Claimed);
+        start.set((scrolled_window.hadjustment().value(), scrolled_window.vadjustment().value()));
+    }));
+    pan.connect_drag_update(clone!(@strong start, @weak scrolled_window => move |_, offset_x, offset_y| {
+        let (start_h, start_v) = start.get();
+        scrolled_window.hadjustment().set_value(start_h - offset_x);
+        scrolled_window.vadjustment().set_value(start_v - offset_y);
+    }));
+    scrolled_window.add_controller(pan);
+}
+
+// remembers whether space is held, for panning; text entries get to see the key first
+pub(crate) fn add_space_tracking(window: &ApplicationWindow) {
+    let keys = EventControllerKey::new();
+    keys.connect_key_pressed(|controller, key, _, _| {
+        if key == gdk::Key::space && !is_typing(&controller.widget()) {
+            let ds = gtk_wrappers::get_application(&controller.widget());
+            ds.imp().space_pressed.set(true);
+        }
+        Propagation::Proceed
+    });
+    keys.connect_key_released(|controller, key, _, _| {
+        if key == gdk::Key::space {
+            let ds = gtk_wrappers::get_application(&controller.widget());
+            ds.imp().space_pressed.set(false);
+        }
+    });
+    window.add_controller(keys);
+}
+
+// spaces typed in the search, annotation or label entries are text, not panning
+fn is_typing(window: &gtk::Widget) -> bool {
+    let Some(focus) = window.root().and_then(|root| root.focus()) else { return false };
+    focus.is::<gtk::Editable>() || focus.is::<gtk::TextView>()
+}
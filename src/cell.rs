@@ -1,13 +1,15 @@
-use gtk::{Align, ApplicationWindow, Fixed, GestureClick, glib, pango, WidgetPaintable};
+use std::path::Path;
+
+use gtk::{Align, ApplicationWindow, Fixed, gio, GestureClick, glib, pango, WidgetPaintable};
 use gtk::EventSequenceState;
 use gtk::gdk::ContentProvider;
 use gtk::glib::Value;
-use gtk::prelude::{BoxExt, Cast, WidgetExt};
+use gtk::prelude::{BoxExt, Cast, EventControllerExt, WidgetExt};
 use gtk::prelude::GestureExt;
 use gtk::prelude::ToVariant;
 use gtk::subclass::prelude::ObjectSubclassIsExt;
 
-use crate::{DRAG_ACTION, files, folder, gtk_wrappers};
+use crate::{cell_menu, connectors, DRAG_ACTION, files, folder, gtk_wrappers, launchers, thumbnails, tooltips};
 use crate::glib::clone;
 use crate::gtk_wrappers::{alert, get_application};
 
@@ -22,15 +24,14 @@ pub(crate) struct DNDInfo {
     pub(crate) grabbed_y: f64,
 }
 
-pub fn make_cell(path: String, dir_item: &files::DirItem, size: i32) -> gtk::Box {
-    let name = dir_item.name.clone();
-    let img = generate_icon(path, &dir_item, size);
-    img.set_tooltip_text(Some(dir_item.name.as_str()));
+pub fn make_cell(dir_item: &files::DirItem, custom_icon: Option<&String>, label: Option<&String>, size: i32) -> gtk::Box {
+    let img = generate_icon(&dir_item, custom_icon, size);
     img.set_halign(Align::Center);
     // img.set_valign(Align::Start);
 
-    let g_text = glib::markup_escape_text(name.as_str());
-    let pango_string = String::from("<span font_size=\"small\" font_weight=\"bold\"  color=\"white\">") + g_text.as_str() + "</span>";
+    let g_text = glib::markup_escape_text(label.cloned().unwrap_or(dir_item.display_name()).as_str());
+    let style = if dir_item.target.is_some() { " font_style=\"italic\"" } else { "" };
+    let pango_string = String::from("<span font_size=\"small\" font_weight=\"bold\"  color=\"white\"") + style + ">" + g_text.as_str() + "</span>";
     let label = gtk::Label::new(Option::Some(pango_string.as_str()));
     label.set_use_markup(true);
     label.set_ellipsize(pango::EllipsizeMode::End);
@@ -39,20 +40,39 @@ pub fn make_cell(path: String, dir_item: &files::DirItem, size: i32) -> gtk::Box
     label.set_lines(2);
     label.set_justify(gtk::Justification::Center);
     label.set_halign(Align::Center);
-    label.set_tooltip_text(Some(dir_item.name.as_str()));
 
     let desktop_icon = gtk::Box::new(gtk::Orientation::Vertical, 10);
-    desktop_icon.set_tooltip_text(Some(dir_item.name.as_str()));
+    desktop_icon.set_widget_name(dir_item.name.as_str());
+    // the image and the label have none of their own, so their tooltip is the one of the cell
+    tooltips::add_rich_tooltip(&desktop_icon);
+    if dir_item.broken_link {
+        desktop_icon.add_css_class("broken_link");
+    }
     desktop_icon.set_homogeneous(false);
     desktop_icon.set_spacing(3);
     desktop_icon.append(&img);
     desktop_icon.append(&label);
     let gesture_click = GestureClick::new();
     let mime_type = dir_item.mime_type.clone();
-    gesture_click.connect_pressed(clone!(@strong mime_type, @weak desktop_icon => @default-return (), move |_, clicks, _, _| {
+    let target = dir_item.target.clone();
+    let broken_link = dir_item.link_target.clone().filter(|_| dir_item.broken_link);
+    gesture_click.connect_pressed(clone!(@strong mime_type, @strong target, @weak desktop_icon => @default-return (), move |_, clicks, _, _| {
+        if clicks == 1 && connectors::finish_linking(&desktop_icon) {
+            return;
+        }
         if clicks == 2 {
             let data_store = get_application(&desktop_icon);
             let current_path = data_store.imp().metafolder.borrow().get_current_path();
+            // shortcuts open their target, the cells of the folder their file, whose name follows renames
+            let target = target.clone().unwrap_or(current_path + desktop_icon.widget_name().as_str());
+            if let Some(link_target) = &broken_link {
+                alert(&desktop_icon, "cannot open".to_string(), format!("{} points to {}, which does not exist", desktop_icon.widget_name(), link_target));
+                return;
+            }
+            if mime_type == launchers::LAUNCHER_MIME {
+                launchers::activate(&desktop_icon, target);
+                return;
+            }
             if mime_type == "inode/directory" {
                 let app = get_application(&desktop_icon);
                 let drilldown = app.imp().drilldown_switch.borrow().as_ref().unwrap().state();
@@ -61,7 +81,8 @@ pub fn make_cell(path: String, dir_item: &files::DirItem, size: i32) -> gtk::Box
                     let app_window_result = root.downcast::<ApplicationWindow>();
                     match app_window_result {
                         Ok(app_win) => {
-                            folder::draw_folder(current_path + name.as_str() + "/", &app_win);
+                            let folder_path = if target.ends_with('/') { target.clone() } else { target.clone() + "/" };
+                            folder::draw_folder(folder_path, &app_win);
                             return
                         }
                         Err(r) => {
@@ -72,30 +93,25 @@ pub fn make_cell(path: String, dir_item: &files::DirItem, size: i32) -> gtk::Box
                 }
             }
             let p_conf = subprocess::PopenConfig {detached: true,setpgid: true,..Default::default()};
-            match subprocess::Popen::create(&["xdg-open", &(current_path.clone() + name.as_str())], p_conf){
+            match subprocess::Popen::create(&["xdg-open", target.as_str()], p_conf){
                 Ok(_) => {}
-                Err(error) => { println!("error opening file {} : {}", current_path + name.as_str(), error) }
+                Err(error) => { println!("error opening file {} : {}", target, error) }
             }
         }
     }));
     desktop_icon.add_controller(gesture_click);
+    desktop_icon.add_controller(cell_menu::make_cell_menu_gesture());
     desktop_icon
 }
 
-fn generate_icon(path: String, dir_item: &files::DirItem, size: i32) -> gtk::Image {
+// a custom icon chosen by the user wins over the icon of the file; a missing custom image falls back to it
+fn generate_icon(dir_item: &files::DirItem, custom_icon: Option<&String>, size: i32) -> gtk::Image {
     let img: gtk::Image;
 
-    if let Some(gicon) = &dir_item.icon {
-        if dir_item.mime_type.starts_with("image") {
-            img = gtk::Image::from_file(path.to_owned() + dir_item.name.as_str());
-        } else {
-            match dir_item.mime_type.as_str() {
-                "application/pdf" => {
-                    img = gtk::Image::from_gicon(gicon)
-                }
-                _ => img = gtk::Image::from_gicon(gicon)
-            }
-        }
+    if let Some(custom_icon) = custom_icon.filter(|c| !c.starts_with('/') || Path::new(c.as_str()).exists()) {
+        img = if custom_icon.starts_with('/') { gtk::Image::from_file(custom_icon) } else { gtk::Image::from_icon_name(custom_icon) };
+    } else if let Some(gicon) = &dir_item.icon {
+        img = gtk::Image::from_gicon(gicon)
     } else {
         img = gtk::Image::from_icon_name("x-office-document");
     }
@@ -103,20 +119,35 @@ fn generate_icon(path: String, dir_item: &files::DirItem, size: i32) -> gtk::Ima
     img
 }
 
-pub(crate) fn make_drag_source(name: String, desktop_icon: &gtk::Box, layout: &Fixed) -> gtk::DragSource {
+// the icon of a cell stands in for its thumbnail until this is ready; also refreshes the thumbnail of a modified file
+pub(crate) fn load_thumbnail(cell: &gtk::Box, path: String, dir_item: &files::DirItem, cancellable: &gio::Cancellable) {
+    let Some(image) = cell.first_child().and_then(|w| w.downcast::<gtk::Image>().ok()) else { return };
+    let file = dir_item.target.clone().unwrap_or(path + dir_item.name.as_str());
+    thumbnails::load_thumbnail(&image, file, dir_item.mime_type.clone(), image.pixel_size(), cancellable);
+}
+
+// the cell is held where the pointer grabbed it, or by its top left corner when dragged from elsewhere, like the arrivals tray
+pub(crate) fn make_drag_source(name: String, desktop_icon: &gtk::Box, layout: &Fixed, grab_at_pointer: bool) -> gtk::DragSource {
     let drag_source = gtk::DragSource::new();
     drag_source.set_actions(DRAG_ACTION);
     let name_copy = String::from(name.as_str());
     let l_clone = layout.clone();
     drag_source.connect_prepare(
-        clone!(@weak  desktop_icon => @default-return None, move |me, x, y| {
+        clone!(@weak  desktop_icon => @default-return None, move |me, pointer_x, pointer_y| {
             let ds = gtk_wrappers::get_application(&desktop_icon);
+            if ds.imp().metafolder.borrow().locked {
+                me.drag_cancel();
+                return None
+            }
             if ds.imp().metafolder.borrow().zoom {
                 me.drag_cancel();
                 alert(&desktop_icon, "Cannot move".to_string(), "Desktop is zoomed - either unzoom or commit to move".to_string());
                 return None
             }
             me.set_state(EventSequenceState::Claimed);
+            let (x, y) = if grab_at_pointer { (pointer_x, pointer_y) } else { (0f64, 0f64) };
+            // kept realized while dragged, however far the desktop scrolls
+            ds.imp().metafolder.borrow().dragged.replace(Some(name_copy.clone()));
             let mut dnd_info  = DNDInfo::default();
             dnd_info.name = name_copy.to_string();
             let actual_bounds = gtk_wrappers::get_widget_bounds(l_clone.as_ref(), &desktop_icon);
@@ -132,5 +163,9 @@ pub(crate) fn make_drag_source(name: String, desktop_icon: &gtk::Box, layout: &F
             Some(ContentProvider::for_value(&Value::from(dnd_info.to_variant())))
         })
     );
+    drag_source.connect_drag_end(|me, _, _| {
+        let ds = gtk_wrappers::get_application(&me.widget());
+        ds.imp().metafolder.borrow().dragged.replace(None);
+    });
     drag_source
 }
\ No newline at end of file
@@ -6,7 +6,7 @@ use gtk::prelude::FixedExt;
 use gtk::glib::Propagation;
 use crate::gtk_wrappers::alert;
 
-pub(crate) fn make_cell_formatter() -> (Popover, Scale, Switch, Scale) {
+pub(crate) fn make_cell_formatter() -> (Popover, Scale, Switch, Scale, Scale) {
     let grid = gtk::Fixed::builder().build();
     let popover = Popover::builder().build();
     popover.set_size_request(300, 50);
@@ -35,7 +35,7 @@ pub(crate) fn make_cell_formatter() -> (Popover, Scale, Switch, Scale) {
 
     text_size_scale.connect_change_value(move |scale, _, val| {
         let ds = gtk_wrappers::get_application(scale);
-        ds.imp().metafolder.borrow().change_font_size(FONT_SIZES[val as usize].to_string(), true);
+        ds.imp().metafolder.borrow().change_font_size(FONT_SIZES[val as usize].to_string(), true, scale);
         Propagation::Proceed
     });
     let bold_container = gtk::Box::builder().orientation(Orientation::Horizontal).build();
@@ -49,7 +49,7 @@ pub(crate) fn make_cell_formatter() -> (Popover, Scale, Switch, Scale) {
             return Propagation::Stop;
         }
         let ds = gtk_wrappers::get_application(sw);
-        if let Some(err) = ds.imp().metafolder.borrow().change_bold(state, true) {
+        if let Some(err) = ds.imp().metafolder.borrow().change_bold(state, true, sw) {
             alert(sw, "folder settings could not be saved".to_string(), err.to_string());
             return Propagation::Stop;
         }
@@ -77,11 +77,38 @@ pub(crate) fn make_cell_formatter() -> (Popover, Scale, Switch, Scale) {
     cell_size_scale.connect_change_value(move |scale, _, val| {
         let ds = gtk_wrappers::get_application(scale);
         let size = CELL_SIZES[val as usize];
-        ds.imp().metafolder.borrow().change_cell_size(size, true);
+        ds.imp().metafolder.borrow().change_cell_size(size, true, scale);
+        Propagation::Proceed
+    });
+
+    let spacing_adjustment = gtk::Adjustment::new(
+        0.0,   // The value where the handle will be at the initial state
+        0.0,   // Lower bound
+        40.0, // Upper bound
+        5.0,   // Step increment, keep it 0 if you don't want it to be operated by arrow keys
+        10.0,   // Page increment
+        0.0,   // Page size
+    );
+    let spacing_scale = Scale::new(gtk::Orientation::Horizontal, Some(&spacing_adjustment));
+    spacing_scale.add_mark(0f64, PositionType::Top, None);
+    spacing_scale.add_mark(20f64, PositionType::Top, None);
+    spacing_scale.add_mark(40f64, PositionType::Top, None);
+    spacing_scale.set_round_digits(0);
+    spacing_scale.set_width_request(300);
+    spacing_scale.set_height_request(50);
+    spacing_scale.set_tooltip_text(Some("adjust minimum spacing between cells"));
+
+    spacing_scale.connect_change_value(move |scale, _, val| {
+        let ds = gtk_wrappers::get_application(scale);
+        let result = ds.imp().metafolder.borrow_mut().set_spacing(val as i32);
+        if let Some(err) = result {
+            alert(scale, "folder settings could not be saved".to_string(), err.to_string());
+        }
         Propagation::Proceed
     });
     grid.put(&text_size_scale, 0f64, 0f64);
     grid.put(&bold_container, 0f64, 50f64);
     grid.put(&cell_size_scale, 0f64, 100f64);
-    (popover, text_size_scale, bold_switch, cell_size_scale)
+    grid.put(&spacing_scale, 0f64, 150f64);
+    (popover, text_size_scale, bold_switch, cell_size_scale, spacing_scale)
 }
\ No newline at end of file
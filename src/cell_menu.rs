@@ -0,0 +1,228 @@
+use gtk::{Button, CheckButton, ColorDialog, ColorDialogButton, DropDown, Entry, FileDialog, FileFilter, GestureClick, Grid, Label, Popover};
+use gtk::gdk;
+use gtk::gdk::RGBA;
+use gtk::gio;
+use gtk::glib;
+use gtk::glib::clone;
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::prelude::{BoxExt, ButtonExt, Cast, CheckButtonExt, EditableExt, EntryExt, EventControllerExt, FileExt, GestureExt, GestureSingleExt, GridExt, IsA, PopoverExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::files::{Anchor, IconStyle};
+use crate::{connectors, gtk_wrappers, launchers, shortcuts, tags};
+use crate::gtk_wrappers::{alert, get_desktop};
+
+const WEIGHTS: &[&str] = &["folder weight", "bold", "normal"];
+const DEFAULT_TILE_COLOR: &str = "rgba(255, 255, 255, 0.25)";
+
+// window edges and corners, laid out as they are on screen
+const ANCHORS: [(Anchor, &str, i32, i32); 9] = [
+    (Anchor::TopLeft, "go-top-symbolic", 0, 0),
+    (Anchor::Top, "go-up-symbolic", 1, 0),
+    (Anchor::TopRight, "go-top-symbolic", 2, 0),
+    (Anchor::Left, "go-previous-symbolic", 0, 1),
+    (Anchor::None, "window-close-symbolic", 1, 1),
+    (Anchor::Right, "go-next-symbolic", 2, 1),
+    (Anchor::BottomLeft, "go-bottom-symbolic", 0, 2),
+    (Anchor::Bottom, "go-down-symbolic", 1, 2),
+    (Anchor::BottomRight, "go-bottom-symbolic", 2, 2),
+];
+
+// right click on a cell opens its menu; the cell is identified by its widget name, which follows renames
+pub(crate) fn make_cell_menu_gesture() -> GestureClick {
+    let gesture = GestureClick::new();
+    gesture.set_button(gdk::BUTTON_SECONDARY);
+    gesture.connect_pressed(|g, _, _, _| {
+        g.set_state(gtk::EventSequenceState::Claimed);
+        let Ok(cell) = g.widget().downcast::<gtk::Box>() else { return };
+        show_cell_menu(&cell);
+    });
+    gesture
+}
+
+fn show_cell_menu(cell: &gtk::Box) {
+    let name = cell.widget_name().to_string();
+    let desktop = get_desktop(cell);
+    let container = gtk::Box::builder().orientation(Vertical).spacing(10).build();
+    let popover = Popover::builder().child(&container).build();
+    container.append(&make_anchor_section(name.clone(), &popover));
+
+    // the popover is not parented to the cell because the label is expected to be the last child of a cell
+    popover.set_parent(&desktop);
+    container.append(&make_icon_section(name.clone(), &popover));
+    container.append(&make_style_section(name.clone(), &popover));
+    container.append(&tags::make_tag_section(name.clone(), &popover));
+    container.append(&connectors::make_connector_section(name.clone(), &popover));
+    let ds = gtk_wrappers::get_application(cell);
+    let mf = ds.imp().metafolder.borrow();
+    let is_shortcut = mf.is_shortcut(name.as_str());
+    // the path of the launcher, the target for a shortcut to one
+    let launcher_path = mf.dir_items.get(&name).filter(|d| d.launcher.is_some())
+        .map(|d| d.target.clone().unwrap_or(mf.get_current_path() + name.as_str()));
+    drop(mf);
+    if let Some(path) = launcher_path {
+        container.append(&launchers::make_launcher_section(name.clone(), path, &popover));
+    }
+    if is_shortcut {
+        container.append(&shortcuts::make_shortcut_section(name, &popover));
+    }
+    let bounds = gtk_wrappers::get_widget_bounds(&desktop, cell);
+    popover.set_pointing_to(Some(&gdk::Rectangle::new(bounds.x() as i32, bounds.y() as i32, bounds.width() as i32, bounds.height() as i32)));
+    popover.connect_closed(|p| {
+        glib::idle_add_local_once(clone!(@weak p => move || p.unparent()));
+    });
+    popover.popup();
+}
+
+fn make_anchor_section(name: String, popover: &Popover) -> gtk::Box {
+    let section = gtk::Box::builder().orientation(Vertical).spacing(5).build();
+    section.append(&Label::builder().label("anchor to window edge").build());
+    let grid = Grid::builder().row_spacing(2).column_spacing(2).halign(gtk::Align::Center).build();
+    for (anchor, icon_name, column, row) in ANCHORS {
+        let button = Button::from_icon_name(icon_name);
+        button.set_tooltip_text(Some(if anchor == Anchor::None { "not anchored" } else { "keep the distance from this edge when the window is resized" }));
+        button.connect_clicked(clone!(@strong name, @weak popover => move |b| {
+            let ds = gtk_wrappers::get_application(b);
+            let mut mf = ds.imp().metafolder.borrow_mut();
+            let names = mf.selection_for(name.as_str());
+            let result = mf.set_anchor(&get_desktop(b), &names, anchor);
+            drop(mf);
+            if let Some(err) = result {
+                alert(b, "folder settings could not be saved".to_string(), err.to_string());
+            }
+            popover.popdown();
+        }));
+        grid.attach(&button, column, row, 1, 1);
+    }
+    section.append(&grid);
+    section.set_tooltip_text(Some("applies to all found icons when the icon is one of them"));
+    section
+}
+
+// an icon theme name typed in, or an image chosen from the disk
+fn make_icon_section(name: String, popover: &Popover) -> gtk::Box {
+    let section = gtk::Box::builder().orientation(Vertical).spacing(5).build();
+    let ds = gtk_wrappers::get_application(popover);
+    let custom_icon = ds.imp().metafolder.borrow().custom_icons.get(&name).cloned();
+    let entry = Entry::builder().placeholder_text("icon name, e.g. folder-music").build();
+    entry.set_text(custom_icon.clone().filter(|c| !c.starts_with('/')).unwrap_or_default().as_str());
+    entry.connect_activate(clone!(@strong name, @weak popover => move |e| {
+        let icon_name = e.text().trim().to_string();
+        if !icon_name.is_empty() {
+            set_custom_icon(e, name.as_str(), Some(icon_name));
+        }
+        popover.popdown();
+    }));
+    section.append(&entry);
+    let change = Button::builder().label("change icon…").build();
+    change.connect_clicked(clone!(@strong name, @weak popover => move |b| {
+        let desktop = get_desktop(b);
+        popover.popdown();
+        let filter = FileFilter::new();
+        filter.add_pixbuf_formats();
+        let dialog = FileDialog::builder().title("choose an icon").modal(true).default_filter(&filter).build();
+        let window = desktop.root().and_then(|r| r.downcast::<gtk::Window>().ok());
+        dialog.open(window.as_ref(), None::<&gio::Cancellable>, clone!(@strong name, @weak desktop => move |result| {
+            let Some(path) = result.ok().and_then(|f| f.path()).and_then(|p| p.to_str().map(|s| s.to_string())) else { return };
+            // another folder was opened meanwhile
+            if desktop.root().is_none() {
+                return;
+            }
+            set_custom_icon(&desktop, name.as_str(), Some(path));
+        }));
+    }));
+    section.append(&change);
+    if custom_icon.is_some() {
+        let reset = Button::builder().label("reset icon").build();
+        reset.connect_clicked(clone!(@weak popover => move |b| {
+            set_custom_icon(b, name.as_str(), None);
+            popover.popdown();
+        }));
+        section.append(&reset);
+    }
+    section
+}
+
+// label, colours and weight of this cell only; unchecked colours and "folder weight" follow the folder settings
+fn make_style_section(name: String, popover: &Popover) -> gtk::Box {
+    let section = gtk::Box::builder().orientation(Vertical).spacing(5).build();
+    let ds = gtk_wrappers::get_application(popover);
+    let mf = ds.imp().metafolder.borrow();
+    let icon_style = mf.icon_styles.get(&name).cloned().unwrap_or_default();
+    let folder_color = mf.label_style.borrow().color.clone();
+    drop(mf);
+
+    let label_entry = Entry::builder().placeholder_text("label, the file keeps its name").build();
+    label_entry.set_text(icon_style.label.clone().unwrap_or_default().as_str());
+    section.append(&label_entry);
+    let (color_check, color_button) = make_color_row(&section, "text colour", icon_style.color.as_ref(), folder_color.as_str(), false);
+    let (background_check, background_button) = make_color_row(&section, "tile colour", icon_style.background.as_ref(), DEFAULT_TILE_COLOR, true);
+    let weight_dropdown = DropDown::from_strings(WEIGHTS);
+    weight_dropdown.set_selected(match icon_style.bold {
+        None => 0,
+        Some(true) => 1,
+        Some(false) => 2,
+    });
+    section.append(&weight_dropdown);
+
+    let buttons = gtk::Box::builder().orientation(Horizontal).spacing(5).build();
+    let apply = Button::builder().label("apply").build();
+    apply.connect_clicked(clone!(@strong name, @weak popover, @weak label_entry, @weak color_check, @weak color_button, @weak background_check, @weak background_button, @weak weight_dropdown => move |b| {
+        let label = label_entry.text().trim().to_string();
+        let icon_style = IconStyle {
+            label: if label.is_empty() || label == name { None } else { Some(label) },
+            color: color_check.is_active().then(|| gtk_wrappers::hex_color(&color_button.rgba())),
+            bold: match weight_dropdown.selected() {
+                1 => Some(true),
+                2 => Some(false),
+                _ => None,
+            },
+            background: background_check.is_active().then(|| background_button.rgba().to_string()),
+        };
+        set_icon_style(b, name.as_str(), icon_style);
+        popover.popdown();
+    }));
+    buttons.append(&apply);
+    if !icon_style.is_empty() {
+        let reset = Button::builder().label("reset style").build();
+        reset.connect_clicked(clone!(@weak popover => move |b| {
+            set_icon_style(b, name.as_str(), IconStyle::default());
+            popover.popdown();
+        }));
+        buttons.append(&reset);
+    }
+    section.append(&buttons);
+    section
+}
+
+// a colour of the cell's own, used only while checked
+fn make_color_row(section: &gtk::Box, label: &str, color: Option<&String>, default_color: &str, with_alpha: bool) -> (CheckButton, ColorDialogButton) {
+    let row = gtk::Box::builder().orientation(Horizontal).spacing(5).build();
+    let check = CheckButton::builder().label(label).active(color.is_some()).build();
+    row.append(&check);
+    let dialog = ColorDialog::builder().modal(true).title(format!("Pick the {}", label).as_str()).with_alpha(with_alpha).build();
+    let button = ColorDialogButton::builder().dialog(&dialog).build();
+    let rgba = RGBA::parse(color.map(|c| c.as_str()).unwrap_or(default_color)).unwrap_or(RGBA::WHITE);
+    button.set_rgba(&rgba);
+    // picking a colour means using it
+    button.connect_rgba_notify(clone!(@weak check => move |_| check.set_active(true)));
+    row.append(&button);
+    section.append(&row);
+    (check, button)
+}
+
+fn set_icon_style(w: &impl IsA<gtk::Widget>, name: &str, icon_style: IconStyle) {
+    let ds = gtk_wrappers::get_application(w);
+    let result = ds.imp().metafolder.borrow_mut().set_icon_style(&get_desktop(w), name, icon_style);
+    if let Some(err) = result {
+        alert(w, "folder settings could not be saved".to_string(), err.to_string());
+    }
+}
+
+fn set_custom_icon(w: &impl IsA<gtk::Widget>, name: &str, custom_icon: Option<String>) {
+    let ds = gtk_wrappers::get_application(w);
+    let result = ds.imp().metafolder.borrow_mut().set_custom_icon(&get_desktop(w), name, custom_icon);
+    if let Some(err) = result {
+        alert(w, "folder settings could not be saved".to_string(), err.to_string());
+    }
+}
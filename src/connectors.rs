@@ -0,0 +1,154 @@
+use gtk::{ApplicationWindow, Button, DrawingArea, EventControllerKey, Fixed, GestureClick, Label, Popover};
+use gtk::gdk;
+use gtk::gdk::RGBA;
+use gtk::glib;
+use gtk::glib::{clone, Propagation};
+use gtk::Orientation::Vertical;
+use gtk::prelude::{BoxExt, ButtonExt, DrawingAreaExtManual, EventControllerExt, IsA, PopoverExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::gtk_wrappers;
+use crate::gtk_wrappers::{alert, flash_cell};
+use crate::metafolder::MetaFolder;
+use crate::spatial::Bounds;
+
+const ARROW_HEAD: f64 = 12f64;
+
+// one drawing area over the whole desktop draws all connectors from the current cell bounds,
+// so they follow the cells whenever these move
+pub(crate) fn draw_connector_layer(desktop: &Fixed) {
+    let layer = DrawingArea::new();
+    // clicks go through to the zones and annotations beneath
+    layer.set_can_target(false);
+    layer.set_draw_func(|area, cr, _, _| {
+        let ds = gtk_wrappers::get_application(area);
+        let Ok(mf) = ds.imp().metafolder.try_borrow() else { return };
+        draw_connectors(&mf, cr);
+    });
+    let ds = gtk_wrappers::get_application(desktop);
+    ds.imp().metafolder.borrow_mut().set_connector_layer(desktop, layer);
+}
+
+fn draw_connectors(mf: &MetaFolder, cr: &gtk::cairo::Context) {
+    let rgba = RGBA::parse(mf.label_style.borrow().color.as_str()).unwrap_or(RGBA::WHITE);
+    cr.set_source_rgba(rgba.red() as f64, rgba.green() as f64, rgba.blue() as f64, rgba.alpha() as f64);
+    cr.set_line_width(2f64);
+    let index = mf.spatial_index.borrow();
+    for connector in &mf.connectors {
+        let (Some(from), Some(to)) = (index.get(connector.from.as_str()), index.get(connector.to.as_str())) else { continue };
+        let (from_x, from_y) = border_point(&from, &to);
+        let (to_x, to_y) = border_point(&to, &from);
+        cr.move_to(from_x, from_y);
+        cr.line_to(to_x, to_y);
+        let angle = (to_y - from_y).atan2(to_x - from_x);
+        for side in [-0.5f64, 0.5f64] {
+            cr.move_to(to_x, to_y);
+            cr.line_to(to_x - ARROW_HEAD * (angle + side).cos(), to_y - ARROW_HEAD * (angle + side).sin());
+        }
+    }
+    let _ = cr.stroke();
+}
+
+// where the line between the centers of two cells leaves the first one
+fn border_point(b: &Bounds, other: &Bounds) -> (f64, f64) {
+    let (center_x, center_y) = (b.x + b.w / 2f64, b.y + b.h / 2f64);
+    let dx = other.x + other.w / 2f64 - center_x;
+    let dy = other.y + other.h / 2f64 - center_y;
+    if dx == 0f64 && dy == 0f64 {
+        return (center_x, center_y);
+    }
+    let t = (b.w / 2f64 / dx.abs()).min(b.h / 2f64 / dy.abs());
+    (center_x + dx * t, center_y + dy * t)
+}
+
+// the connector section of the cell menu
+pub(crate) fn make_connector_section(name: String, popover: &Popover) -> gtk::Box {
+    let section = gtk::Box::builder().orientation(Vertical).spacing(5).build();
+    let link = Button::builder().label("link to…").build();
+    link.set_tooltip_text(Some("then click the icon to link to"));
+    link.connect_clicked(clone!(@strong name, @weak popover => move |b| {
+        let ds = gtk_wrappers::get_application(b);
+        ds.imp().metafolder.borrow_mut().linking_from = Some(name.clone());
+        popover.popdown();
+    }));
+    section.append(&link);
+
+    let ds = gtk_wrappers::get_application(popover);
+    let count = ds.imp().metafolder.borrow().connectors.iter().filter(|c| c.from == name || c.to == name).count();
+    if count > 0 {
+        let unlink = Button::builder().label(format!("remove {} link(s)", count).as_str()).build();
+        unlink.connect_clicked(clone!(@weak popover => move |b| {
+            let ds = gtk_wrappers::get_application(b);
+            let result = ds.imp().metafolder.borrow_mut().remove_connectors_of(name.as_str());
+            if let Some(err) = result {
+                alert(b, "folder settings could not be saved".to_string(), err.to_string());
+            }
+            popover.popdown();
+        }));
+        section.append(&unlink);
+    } else {
+        section.append(&Label::new(Some("no links")));
+    }
+    section
+}
+
+// a link being made is given up with Escape or a click next to the icons
+pub(crate) fn add_linking_cancel(desktop: &Fixed) {
+    let click = GestureClick::new();
+    // cells see the click first, so a link to a cell is made before this
+    click.connect_pressed(|gesture, _, _, _| {
+        cancel_linking(&gesture.widget());
+    });
+    desktop.add_controller(click);
+}
+
+pub(crate) fn add_linking_escape(window: &ApplicationWindow) {
+    let keys = EventControllerKey::new();
+    keys.connect_key_pressed(|controller, key, _, _| {
+        if key == gdk::Key::Escape && cancel_linking(&controller.widget()) {
+            return Propagation::Stop;
+        }
+        Propagation::Proceed
+    });
+    window.add_controller(keys);
+}
+
+// false when no link was being made
+fn cancel_linking(w: &impl IsA<gtk::Widget>) -> bool {
+    let ds = gtk_wrappers::get_application(w);
+    let Ok(mut mf) = ds.imp().metafolder.try_borrow_mut() else { return false };
+    mf.linking_from.take().is_some()
+}
+
+// completes a connector started from the cell menu; returns false when no connector is being created
+pub(crate) fn finish_linking(cell: &impl IsA<gtk::Widget>) -> bool {
+    let ds = gtk_wrappers::get_application(cell);
+    let Some(from) = ds.imp().metafolder.borrow_mut().linking_from.take() else { return false };
+    let to = cell.widget_name().to_string();
+    let result = ds.imp().metafolder.borrow_mut().add_connector(from.as_str(), to.as_str());
+    if let Some(err) = result {
+        alert(cell, "folder settings could not be saved".to_string(), err.to_string());
+    }
+    let mf = ds.imp().metafolder.borrow();
+    if let Some(cell) = mf.cell_map.get(&to) {
+        flash_cell(cell, "icon_found");
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::border_point;
+    use crate::spatial::Bounds;
+
+    #[test]
+    fn border_point_is_where_the_line_leaves_the_cell() {
+        let b = Bounds::new(0f64, 0f64, 100f64, 50f64);
+        assert_eq!(border_point(&b, &Bounds::new(300f64, 0f64, 100f64, 50f64)), (100f64, 25f64));
+        assert_eq!(border_point(&b, &Bounds::new(0f64, 200f64, 100f64, 50f64)), (50f64, 50f64));
+        assert_eq!(border_point(&b, &Bounds::new(-300f64, 0f64, 100f64, 50f64)), (0f64, 25f64));
+        // diagonally the nearer border wins: the bottom of a wide cell
+        assert_eq!(border_point(&b, &Bounds::new(100f64, 100f64, 100f64, 50f64)), (75f64, 50f64));
+        assert_eq!(border_point(&b, &b), (50f64, 25f64));
+    }
+}
@@ -1,15 +1,16 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::fs;
 use std::io::Read;
 use std::io::Write;
 use std::path::Path;
 
-use gtk::gio;
-use gtk::gio::{Cancellable, FileInfo, FileType};
-use gtk::prelude::FileExt;
+use gtk::{gio, glib};
+use gtk::gio::{FileInfo, FileType};
+use gtk::prelude::{AppInfoExt, Cast, FileExt};
 use ignore::Error;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use serde::{Deserialize, Serialize};
-use crate::DEFAULT_BG_COLOR;
+use crate::{DEFAULT_BG_COLOR, launchers, tags};
 
 pub(crate) fn try_file(path: &str) -> bool {
     Path::new(path).exists()
@@ -27,88 +28,425 @@ pub(crate) fn home_path() -> String {
     }
 }
 
-pub(crate) fn get_entries(p: String) -> HashSet<DirItem> {
-    let mut entries: HashSet<DirItem> = HashSet::new();
-    let paths = fs::read_dir(p).expect("Impossible to get your home dir!");
-
-    for path in paths {
-        let dir_entry = path.expect("Impossible to get your home dir!").path();
-        let file_name_opt = dir_entry.file_name();
-        match file_name_opt {
-            Some(f) => {
-                let file_name_string_opt = f.to_str();
-                match file_name_string_opt {
-                    Some(_) => {
-                        let dir_item_opt = get_file_info(dir_entry.as_path().to_str().expect("Fatal: get complete path").to_string());
-                        match dir_item_opt {
-                            Some(dir_item) => {
-                                entries.insert(dir_item);
-                            }
-                            None => continue
-                        }
-                    }
-                    None => panic!("Impossible to get your home dir!"),
-                }
-            }
-            None => panic!("Impossible to get your home dir!"),
-        }
-    }
-    entries
-}
+// what the cells of a folder need to know about its entries, and nothing more
+pub(crate) const ENTRY_ATTRIBUTES: &str = "standard::name,standard::type,standard::is-hidden,standard::is-symlink,standard::symlink-target,standard::icon,standard::content-type,time::modified,access::can-read,access::can-write,xattr::xdg.tags";
 
-#[derive(Eq, Hash, PartialEq, Default)]
+#[derive(Eq, Hash, PartialEq, Default, Clone, Debug)]
 pub struct DirItem {
     pub(crate) name: String,
     pub(crate) is_dir: bool,
     pub(crate) mime_type: String,
     pub(crate) icon: Option<gio::Icon>,
+    // seconds since the epoch
+    pub(crate) modified: i64,
+    // what a shortcut opens; None for the files of the folder
+    pub(crate) target: Option<String>,
+    pub(crate) is_hidden: bool,
+    pub(crate) is_symlink: bool,
+    // where a symlink points to, as written in the link
+    pub(crate) link_target: Option<String>,
+    pub(crate) broken_link: bool,
+    pub(crate) read_only: bool,
+    pub(crate) unreadable: bool,
+    // tags other tools left in the extended attributes of the file
+    pub(crate) xattr_tags: Vec<String>,
+    // the parsed .desktop file of a launcher
+    pub(crate) launcher: Option<gio::DesktopAppInfo>,
+    // a launcher the user allowed to run
+    pub(crate) trusted: bool,
 }
 
+impl DirItem {
+    // launchers show their localized name, other files their file name
+    pub(crate) fn display_name(&self) -> String {
+        match &self.launcher {
+            Some(launcher) => launcher.name().to_string(),
+            None => self.name.clone(),
+        }
+    }
+}
 
-pub(crate) fn get_file_info(path_name: String) -> Option<DirItem> {
-    let mut dir_item: DirItem = DirItem::default();
+// read without blocking, with its links followed and its launcher read
+pub(crate) async fn get_file_info(path_name: String) -> Option<DirItem> {
     let g_file = gio::File::for_path(path_name.clone());
-
-    let g_file_info_result = g_file.query_info("*", gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS, Cancellable::NONE);
-    let g_file_info: FileInfo;
-    match g_file_info_result {
+    match g_file.query_info_future(ENTRY_ATTRIBUTES, gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS, glib::Priority::DEFAULT).await {
         Ok(file_info) => {
-            g_file_info = file_info;
+            let folder = Path::new(path_name.as_str()).parent().and_then(|p| p.to_str()).unwrap_or_default().to_string() + "/";
+            Some(resolve_dir_item(dir_item_from_info(&file_info), folder).await)
         }
         Err(error) => {
             println!("error getting file info: {}", error);
-            return Option::None;
+            None
         }
     }
+}
 
-    if g_file_info.is_hidden() {
-        return Option::None;
+// links are followed and launchers read by resolve_dir_item
+pub(crate) fn dir_item_from_info(g_file_info: &FileInfo) -> DirItem {
+    let mut dir_item: DirItem = DirItem {
+        name: g_file_info.name().to_str().expect("Fatal: gio cannot get path").to_string(),
+        is_hidden: g_file_info.is_hidden(),
+        ..Default::default()
     };
-    dir_item.name = g_file_info.name().to_str().expect("Fatal: gio cannot get path").to_string();
     if g_file_info.file_type() == FileType::Directory {
         dir_item.is_dir = true;
     }
     match g_file_info.icon() {
         Some(g_icon) => { dir_item.icon = Option::Some(g_icon) }
         None => {
-            println!("cannot find icon for {}", path_name);
+            println!("cannot find icon for {}", dir_item.name);
             dir_item.icon = Option::None
         }
     }
+    if let Some(modified) = g_file_info.modification_date_time() {
+        dir_item.modified = modified.to_unix();
+    }
     match g_file_info.content_type() {
         Some(gmime) => { dir_item.mime_type = gmime.as_str().to_string() }
         None => {
             dir_item.mime_type = String::from("");
-            println!("cannot find mime type for  {}", path_name)
+            println!("cannot find mime type for  {}", dir_item.name)
+        }
+    }
+    dir_item.is_symlink = g_file_info.is_symlink();
+    if dir_item.is_symlink {
+        dir_item.link_target = g_file_info.symlink_target().map(|t| t.to_string_lossy().to_string());
+    }
+    // file systems that do not tell are taken as allowing
+    dir_item.read_only = g_file_info.has_attribute("access::can-write") && !g_file_info.boolean("access::can-write");
+    dir_item.unreadable = g_file_info.has_attribute("access::can-read") && !g_file_info.boolean("access::can-read");
+    if let Some(tags) = g_file_info.attribute_string(tags::TAGS_ATTRIBUTE) {
+        dir_item.xattr_tags = tags::parse_tags(tags.as_str());
+    }
+    dir_item
+}
+
+// off the main thread: a link to a dead mount must not freeze the window; folder ends with a slash
+pub(crate) async fn resolve_dir_item(mut dir_item: DirItem, folder: String) -> DirItem {
+    let path = folder + dir_item.name.as_str();
+    if dir_item.is_symlink {
+        let target = gio::File::for_path(path.as_str()).query_info_future(ENTRY_ATTRIBUTES, gio::FileQueryInfoFlags::NONE, glib::Priority::DEFAULT).await;
+        resolve_symlink(&mut dir_item, target.ok());
+    }
+    if dir_item.mime_type == launchers::LAUNCHER_MIME {
+        dir_item.launcher = launchers::read_launcher(path.as_str()).await;
+        dir_item.trusted = dir_item.launcher.is_some() && launchers::is_trusted(path.as_str()).await;
+        if let Some(icon) = dir_item.launcher.as_ref().and_then(|l| l.icon()) {
+            dir_item.icon = Some(icon);
+        }
+    }
+    dir_item
+}
+
+// a symlink is shown as what it points to, so that linked folders drill down; a broken one keeps the type of a link
+fn resolve_symlink(dir_item: &mut DirItem, target: Option<FileInfo>) {
+    match target {
+        Some(target) => {
+            dir_item.is_dir = target.file_type() == FileType::Directory;
+            if let Some(mime_type) = target.content_type() {
+                dir_item.mime_type = mime_type.to_string();
+            }
+            if let Some(icon) = target.icon() {
+                dir_item.icon = Some(icon);
+            }
+            dir_item.read_only = target.has_attribute("access::can-write") && !target.boolean("access::can-write");
+            dir_item.unreadable = target.has_attribute("access::can-read") && !target.boolean("access::can-read");
         }
+        None => {
+            dir_item.broken_link = true;
+            dir_item.icon = Some(gio::ThemedIcon::new("image-missing").upcast());
+        }
+    }
+}
+
+// the item of a shortcut: its target when it exists, named after the shortcut
+pub(crate) async fn get_shortcut_info(shortcut: MemoShortcut) -> DirItem {
+    let mut dir_item = if shortcut.is_url() {
+        let scheme = shortcut.target.split("://").next().unwrap_or_default();
+        DirItem {
+            mime_type: format!("x-scheme-handler/{}", scheme),
+            icon: Some(gio::ThemedIcon::from_names(&["web-browser", "text-html"]).upcast()),
+            ..DirItem::default()
+        }
+    } else {
+        // a missing target keeps its place on the desktop, with the generic icon
+        get_file_info(shortcut.target.clone()).await.unwrap_or_default()
+    };
+    dir_item.name = shortcut.name;
+    dir_item.target = Some(shortcut.target);
+    dir_item
+}
+
+// window edge or corner an icon keeps its distance from when the window is resized
+#[derive(Eq, Hash, PartialEq, Default, Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Anchor {
+    #[default]
+    None,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Anchor {
+    pub(crate) fn is_left(&self) -> bool {
+        matches!(self, Anchor::Left | Anchor::TopLeft | Anchor::BottomLeft)
+    }
+
+    pub(crate) fn is_right(&self) -> bool {
+        matches!(self, Anchor::Right | Anchor::TopRight | Anchor::BottomRight)
+    }
+
+    pub(crate) fn is_top(&self) -> bool {
+        matches!(self, Anchor::Top | Anchor::TopLeft | Anchor::TopRight)
+    }
+
+    pub(crate) fn is_bottom(&self) -> bool {
+        matches!(self, Anchor::Bottom | Anchor::BottomLeft | Anchor::BottomRight)
     }
-    Some(dir_item)
 }
 
-#[derive(Eq, Hash, PartialEq, Default, Serialize, Deserialize, Debug)]
+#[derive(Eq, Hash, PartialEq, Default, Serialize, Deserialize, Debug, Clone)]
 pub struct MemoIcon {
     pub(crate) position_x: i32,
     pub(crate) position_y: i32,
+    #[serde(default)]
+    pub(crate) anchor: Anchor,
+    // distance of the icon from the anchored edges, in window pixels
+    #[serde(default)]
+    pub(crate) anchor_offset_x: i32,
+    #[serde(default)]
+    pub(crate) anchor_offset_y: i32,
+    // an image file or an icon theme name shown instead of the icon of the file
+    #[serde(default)]
+    pub(crate) custom_icon: Option<String>,
+    #[serde(default)]
+    pub(crate) style: IconStyle,
+}
+
+// looks of one icon that override those of the folder; None keeps the folder's
+#[derive(Eq, Hash, PartialEq, Default, Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct IconStyle {
+    // shown instead of the file name, which stays as it is
+    pub(crate) label: Option<String>,
+    // hex colour of the label
+    pub(crate) color: Option<String>,
+    pub(crate) bold: Option<bool>,
+    // colour of the tile behind the icon and label
+    pub(crate) background: Option<String>,
+}
+
+impl IconStyle {
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == IconStyle::default()
+    }
+}
+
+#[derive(Eq, PartialEq, Default, Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum DropPolicy {
+    // the dragged icon snaps back
+    #[default]
+    Reject,
+    // the dragged icon and the one underneath trade places
+    Swap,
+    // the icon underneath is nudged to the nearest free spot
+    Push,
+}
+
+impl DropPolicy {
+    pub(crate) fn from_index(index: u32) -> DropPolicy {
+        match index {
+            1 => DropPolicy::Swap,
+            2 => DropPolicy::Push,
+            _ => DropPolicy::Reject,
+        }
+    }
+
+    pub(crate) fn index(&self) -> u32 {
+        match self {
+            DropPolicy::Reject => 0,
+            DropPolicy::Swap => 1,
+            DropPolicy::Push => 2,
+        }
+    }
+
+    // what a drop onto the given cells does; a swap is only possible with a single cell
+    pub(crate) fn resolve(&self, mut occupants: Vec<String>) -> DropOutcome {
+        occupants.sort();
+        match self {
+            _ if occupants.is_empty() => DropOutcome::Move,
+            DropPolicy::Swap if occupants.len() == 1 => DropOutcome::Swap(occupants.remove(0)),
+            DropPolicy::Reject | DropPolicy::Swap => DropOutcome::Blocked(occupants),
+            DropPolicy::Push => DropOutcome::Push(occupants),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub(crate) enum DropOutcome {
+    // nothing underneath, the dragged icon is just moved
+    Move,
+    // the dragged icon snaps back, the ones underneath are flashed
+    Blocked(Vec<String>),
+    // the icon underneath takes the place the dragged one left
+    Swap(String),
+    // the icons underneath are nudged to the nearest free spots
+    Push(Vec<String>),
+}
+
+#[derive(Eq, PartialEq, Default, Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum PlacementStrategy {
+    // first free spot, sweeping rows of INITIAL_DESKTOP_WIDTH
+    #[default]
+    FirstFree,
+    // first free spot, sweeping rows as wide as the window
+    VisibleWidth,
+    // first free spot inside MemoFolder.inbox
+    Inbox,
+    // next to the icons with the same mime type
+    SameType,
+    // below the existing layout, new files sorted by name
+    EndByName,
+    // below the existing layout, new files sorted by modification time
+    EndByDate,
+    // inside the first zone accepting the file, else as FirstFree
+    Zone,
+}
+
+impl PlacementStrategy {
+    pub(crate) fn from_index(index: u32) -> PlacementStrategy {
+        match index {
+            1 => PlacementStrategy::VisibleWidth,
+            2 => PlacementStrategy::Inbox,
+            3 => PlacementStrategy::SameType,
+            4 => PlacementStrategy::EndByName,
+            5 => PlacementStrategy::EndByDate,
+            6 => PlacementStrategy::Zone,
+            _ => PlacementStrategy::FirstFree,
+        }
+    }
+
+    pub(crate) fn index(&self) -> u32 {
+        match self {
+            PlacementStrategy::FirstFree => 0,
+            PlacementStrategy::VisibleWidth => 1,
+            PlacementStrategy::Inbox => 2,
+            PlacementStrategy::SameType => 3,
+            PlacementStrategy::EndByName => 4,
+            PlacementStrategy::EndByDate => 5,
+            PlacementStrategy::Zone => 6,
+        }
+    }
+}
+
+// area of the desktop, unzoomed like the positions of the cells
+#[derive(Eq, PartialEq, Default, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct MemoRect {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+}
+
+// titled, coloured area of the desktop drawn beneath the icons; position and size are unzoomed
+#[derive(Eq, PartialEq, Default, Serialize, Deserialize, Debug, Clone)]
+pub struct MemoZone {
+    pub(crate) title: String,
+    pub(crate) color: String,
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+    // comma separated mime type prefixes or extensions of new files placed in the zone, e.g. "image/, .pdf"
+    #[serde(default)]
+    pub(crate) accepts: String,
+}
+
+impl MemoZone {
+    pub(crate) fn accepts(&self, dir_item: &DirItem) -> bool {
+        self.accepts.split(',')
+            .map(|pattern| pattern.trim().to_lowercase())
+            .filter(|pattern| !pattern.is_empty())
+            .any(|pattern| if pattern.starts_with('.') {
+                dir_item.name.to_lowercase().ends_with(pattern.as_str())
+            } else {
+                dir_item.mime_type.starts_with(pattern.as_str())
+            })
+    }
+}
+
+#[derive(Eq, Hash, PartialEq, Default, Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationKind {
+    #[default]
+    Text,
+    Heading,
+    Note,
+    Arrow,
+    Line,
+}
+
+// free-form item drawn on the desktop, position unzoomed; width and height are the size of a note
+// or the vector from start to end of an arrow or line
+#[derive(Eq, PartialEq, Default, Serialize, Deserialize, Debug, Clone)]
+pub struct MemoAnnotation {
+    pub(crate) kind: AnnotationKind,
+    #[serde(default)]
+    pub(crate) text: String,
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    #[serde(default)]
+    pub(crate) width: i32,
+    #[serde(default)]
+    pub(crate) height: i32,
+}
+
+// directed link between two cells, by file name
+#[derive(Eq, Hash, PartialEq, Default, Serialize, Deserialize, Debug, Clone)]
+pub struct MemoConnector {
+    pub(crate) from: String,
+    pub(crate) to: String,
+}
+
+// item shown in the folder without being in it: a file or folder elsewhere, or a URL
+#[derive(Eq, PartialEq, Default, Serialize, Deserialize, Debug, Clone)]
+pub struct MemoShortcut {
+    // unique among the cells of the folder
+    pub(crate) name: String,
+    // absolute path or URL
+    pub(crate) target: String,
+}
+
+impl MemoShortcut {
+    pub(crate) fn is_url(&self) -> bool {
+        self.target.contains("://")
+    }
+}
+
+// a named place in the folder to come back to: scroll offset plus zoom and visual zoom
+#[derive(PartialEq, Default, Serialize, Deserialize, Debug, Clone)]
+pub struct MemoViewpoint {
+    pub(crate) name: String,
+    pub(crate) scroll_x: i32,
+    pub(crate) scroll_y: i32,
+    pub(crate) zoom: bool,
+    pub(crate) zoom_x: i32,
+    pub(crate) zoom_y: i32,
+    // viewpoints saved before the visual zoom was kept are unscaled
+    #[serde(default = "unscaled")]
+    pub(crate) view_scale: f64,
+}
+
+fn unscaled() -> f64 {
+    1f64
 }
 
 #[derive(Default, Serialize, Deserialize, Debug)]
@@ -123,6 +461,68 @@ pub struct MemoFolder {
     pub(crate) zoom_x: i32,
     pub(crate) zoom_y: i32,
     pub(crate) icons: HashMap<String, MemoIcon>,
+    #[serde(default)]
+    pub(crate) drop_policy: DropPolicy,
+    #[serde(default)]
+    pub(crate) spacing: i32,
+    #[serde(default)]
+    pub(crate) placement: PlacementStrategy,
+    #[serde(default)]
+    pub(crate) inbox: Option<MemoRect>,
+    #[serde(default)]
+    pub(crate) locked: bool,
+    #[serde(default)]
+    pub(crate) viewpoints: Vec<MemoViewpoint>,
+    #[serde(default)]
+    pub(crate) zones: Vec<MemoZone>,
+    #[serde(default)]
+    pub(crate) annotations: Vec<MemoAnnotation>,
+    #[serde(default)]
+    pub(crate) connectors: Vec<MemoConnector>,
+    #[serde(default)]
+    pub(crate) shortcuts: Vec<MemoShortcut>,
+    // tags of the icons, by file name
+    #[serde(default)]
+    pub(crate) tags: HashMap<String, Vec<String>>,
+    // tags are written to the extended attributes of the files too
+    #[serde(default)]
+    pub(crate) xattr_tags: bool,
+    #[serde(default)]
+    pub(crate) show_hidden: bool,
+}
+
+pub(crate) const IGNORE_FILE: &str = ".metafolderignore";
+// left out of folders without an ignore file
+const DEFAULT_IGNORE_RULES: [&str; 1] = ["*tmp"];
+
+// files matching the gitignore style rules of the .metafolderignore of the folder get no cell
+pub(crate) fn load_ignore_rules(path: &str) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(path);
+    let ignore_file = path.to_string() + IGNORE_FILE;
+    if try_file(ignore_file.as_str()) {
+        if let Some(err) = builder.add(ignore_file) {
+            println!("error in {}{}: {}", path, IGNORE_FILE, err);
+        }
+    } else {
+        for rule in DEFAULT_IGNORE_RULES {
+            let _ = builder.add_line(None, rule);
+        }
+    }
+    builder.build().unwrap_or_else(|err| {
+        println!("error in {}{}: {}", path, IGNORE_FILE, err);
+        Gitignore::empty()
+    })
+}
+
+// hidden files only when asked, never the settings of the folder
+pub(crate) fn is_shown(dir_item: &DirItem, show_hidden: bool, ignore_rules: &Gitignore) -> bool {
+    if dir_item.name == ".metafolder" {
+        return false;
+    }
+    if dir_item.is_hidden && !show_hidden {
+        return false;
+    }
+    !ignore_rules.matched(dir_item.name.as_str(), dir_item.is_dir).is_ignore()
 }
 
 pub(crate) fn save_settings(path: String, memo_desktop: MemoFolder) -> Option<Error> {
@@ -192,4 +592,66 @@ pub fn up(path: &String) -> Option<String> {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{DirItem, DropOutcome, DropPolicy, IGNORE_FILE, load_ignore_rules, MemoZone};
+
+    fn temp_folder(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("metafolder-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path.to_str().unwrap().to_string() + "/"
+    }
+
+    #[test]
+    fn ignore_rules_default_to_temporary_files() {
+        let folder = temp_folder("default-rules");
+        let rules = load_ignore_rules(folder.as_str());
+        assert!(rules.matched("draft.tmp", false).is_ignore());
+        assert!(!rules.matched("notes.txt", false).is_ignore());
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn ignore_rules_are_read_from_the_folder() {
+        let folder = temp_folder("folder-rules");
+        fs::write(folder.clone() + IGNORE_FILE, "*.log\nbuild/\n").unwrap();
+        let rules = load_ignore_rules(folder.as_str());
+        assert!(rules.matched("run.log", false).is_ignore());
+        assert!(rules.matched("build", true).is_ignore());
+        assert!(!rules.matched("build", false).is_ignore());
+        // the rules of the folder replace the default ones
+        assert!(!rules.matched("draft.tmp", false).is_ignore());
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn zones_accept_mime_type_prefixes_and_extensions() {
+        let zone = MemoZone { accepts: "image/, .PDF ,, ".to_string(), ..Default::default() };
+        let item = |name: &str, mime_type: &str| DirItem { name: name.to_string(), mime_type: mime_type.to_string(), ..Default::default() };
+        assert!(zone.accepts(&item("photo.jpg", "image/jpeg")));
+        // extensions are compared without regard to case
+        assert!(zone.accepts(&item("Report.pdf", "application/octet-stream")));
+        assert!(!zone.accepts(&item("notes.txt", "text/plain")));
+        assert!(!zone.accepts(&item("pdf", "text/plain")));
+        // a zone without patterns takes nothing
+        assert!(!MemoZone::default().accepts(&item("photo.jpg", "image/jpeg")));
+    }
+
+    #[test]
+    fn drop_policies_resolve_the_cells_underneath() {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<String>>();
+        for policy in [DropPolicy::Reject, DropPolicy::Swap, DropPolicy::Push] {
+            assert_eq!(policy.resolve(vec![]), DropOutcome::Move);
+        }
+        assert_eq!(DropPolicy::Reject.resolve(names(&["a"])), DropOutcome::Blocked(names(&["a"])));
+        assert_eq!(DropPolicy::Swap.resolve(names(&["a"])), DropOutcome::Swap("a".to_string()));
+        assert_eq!(DropPolicy::Push.resolve(names(&["b", "a"])), DropOutcome::Push(names(&["a", "b"])));
+        // there is one place to trade, so a drop over several cells is refused
+        assert_eq!(DropPolicy::Swap.resolve(names(&["b", "a"])), DropOutcome::Blocked(names(&["a", "b"])));
+    }
+}
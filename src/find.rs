@@ -7,7 +7,7 @@ use crate::gtk_wrappers;
 
 pub(crate) fn make_find() -> (SearchEntry, Label) {
     let find_box = SearchEntry::builder().placeholder_text("search").build();
-    find_box.set_tooltip_text(Some("find a cell by name - enter/escape"));
+    find_box.set_tooltip_text(Some("find a cell by name, or by tag with #tag - enter/escape"));
     find_box.set_width_request(300);
 
     let find_results = Label::new(None);
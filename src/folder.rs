@@ -1,24 +1,31 @@
 use crate::gtk_wrappers::{set_cell_size_scale, set_font_bold_switch, set_font_color_button, set_font_size_scale};
 use crate::glib::clone;
 use crate::glib;
-use crate::gtk_wrappers::{is_something_underneath, set_title_path};
-use std::collections::{HashMap, HashSet};
+use crate::gtk_wrappers::{alert, flash_cell, is_auto_fit_enabled, set_drop_policy_dropdown, set_lock_widgets, set_placement_dropdown, set_show_hidden_button, set_spacing_scale, set_title_path};
 
-use gtk::{ApplicationWindow, Fixed, gio};
+use gtk::{ApplicationWindow, DrawingArea, Fixed, gio, Overlay, ScrolledWindow};
 use gtk::gdk::RGBA;
-use gtk::gio::{Cancellable, File, FileMonitorEvent, FileMonitorFlags};
+use gtk::gio::{Cancellable, CancellableFuture, File, FileMonitorEvent, FileMonitorFlags, FileQueryInfoFlags};
 use gtk::glib::Value;
-use gtk::prelude::{Cast, FileExt, FileMonitorExt, FixedExt, IsA, WidgetExt};
+use gtk::prelude::{AdjustmentExt, BoxExt, CancellableExt, Cast, DrawingAreaExt, FileEnumeratorExt, FileExt, FileExtManual, FileMonitorExt, FixedExt, IsA, ObjectExt, WidgetExt};
 use gtk::prelude::GtkWindowExt;
 use gtk::subclass::prelude::ObjectSubclassIsExt;
 
-use crate::{cell, DRAG_ACTION, DROP_TYPE, files, gtk_wrappers, ICON_SIZE, INITIAL_DESKTOP_WIDTH};
-use crate::files::MemoFolder;
+use crate::{annotations, arrivals, canvas, cell, connectors, launchers, minimap, zones, DRAG_ACTION, DROP_TYPE, files, gtk_wrappers, zoom};
+use crate::files::{Anchor, DirItem, DropOutcome, MemoFolder, MemoShortcut};
 use crate::gtk_wrappers::{set_bgcolor_button_color, set_drilldown_switch, set_window_background, set_zoom_widgets};
 use crate::metafolder::MetaFolder;
+use crate::spatial::Bounds;
 
+// cells added to the desktop at a time while a folder loads
+const LOAD_BATCH_SIZE: i32 = 100;
+
+// shows the folder right away and fills it with its cells as they are read
 pub(crate) fn draw_folder(path: String, window: &ApplicationWindow) {
-    let entries = files::get_entries(path.clone());
+    let data_store = gtk_wrappers::get_application(window);
+    data_store.imp().metafolder.borrow().load_cancellable.cancel();
+    // a link started in the previous folder is given up
+    data_store.imp().metafolder.borrow_mut().linking_from = None;
 
     let desktop = gtk::Fixed::new();
     let memo_folder = files::load_settings(path.clone());
@@ -31,29 +38,74 @@ pub(crate) fn draw_folder(path: String, window: &ApplicationWindow) {
     metafolder.zoom = memo_folder.zoom;
     metafolder.zoom_x = memo_folder.zoom_x;
     metafolder.zoom_y = memo_folder.zoom_y;
-    let (cell_map, new_entries) = draw_icons(path.clone(), entries, &desktop, INITIAL_DESKTOP_WIDTH, ICON_SIZE, &memo_folder);
-
-    metafolder.cell_map = cell_map;
-    metafolder.added_cells = new_entries;
+    metafolder.drop_policy = memo_folder.drop_policy;
+    metafolder.spacing = memo_folder.spacing;
+    metafolder.placement = memo_folder.placement;
+    metafolder.inbox = memo_folder.inbox;
+    metafolder.locked = memo_folder.locked;
+    metafolder.viewpoints = memo_folder.viewpoints.clone();
+    metafolder.connectors = memo_folder.connectors.clone();
+    metafolder.shortcuts = memo_folder.shortcuts.clone();
+    metafolder.tags = memo_folder.tags.clone();
+    metafolder.xattr_tags = memo_folder.xattr_tags;
+    metafolder.show_hidden = memo_folder.show_hidden;
+    metafolder.ignore_rules = files::load_ignore_rules(path.as_str());
+    // the saved positions of all cells, so that none is lost when saving before all are loaded
+    metafolder.positions.replace(memo_folder.icons.iter()
+        .map(|(name, memo_icon)| (name.clone(), (memo_icon.position_x as f64, memo_icon.position_y as f64)))
+        .collect());
+    metafolder.anchors.replace(memo_folder.icons.iter()
+        .filter(|(_, memo_icon)| memo_icon.anchor != Anchor::None)
+        .map(|(name, memo_icon)| (name.clone(), (memo_icon.anchor, memo_icon.anchor_offset_x, memo_icon.anchor_offset_y)))
+        .collect());
+    metafolder.custom_icons = memo_folder.icons.iter()
+        .filter_map(|(name, memo_icon)| Some((name.clone(), memo_icon.custom_icon.clone()?)))
+        .collect();
+    metafolder.icon_styles = memo_folder.icons.iter()
+        .filter(|(_, memo_icon)| !memo_icon.style.is_empty())
+        .map(|(name, memo_icon)| (name.clone(), memo_icon.style.clone()))
+        .collect();
     let scrolled_window = gtk::ScrolledWindow::new();
+    scrolled_window.set_hexpand(true);
     scrolled_window.set_child(Option::<&gtk::Fixed>::Some(&desktop));
-    window.set_child(Option::Some(&scrolled_window));
+    zoom::add_visual_zoom_controllers(&scrolled_window);
+    add_anchor_handler(&scrolled_window);
+    add_virtualization_handler(&scrolled_window);
+    canvas::add_panning(&scrolled_window);
+    let (overlay, minimap) = minimap::make_minimap(&scrolled_window);
+    add_resize_handler(&overlay);
+    let (arrivals_revealer, arrivals_list) = arrivals::make_arrivals_tray();
+    let content = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+    content.append(&overlay);
+    content.append(&arrivals_revealer);
+    window.set_child(Option::Some(&content));
 
     let drop_target = gtk::DropTarget::new(DROP_TYPE, DRAG_ACTION);
     drop_target.connect_drop(clone!(@weak desktop => @default-return false, move |_drop_target, dnd_msg, x, y| {
         drop_action(dnd_msg, &desktop, x, y)
     }));
+    canvas::add_auto_scroll(&drop_target, &desktop);
+    connectors::add_linking_cancel(&desktop);
     desktop.add_controller(drop_target);
 
-    let data_store = gtk_wrappers::get_application(window);
     data_store.imp().metafolder.replace(metafolder);
+    data_store.imp().arrivals_revealer.replace(Some(arrivals_revealer));
+    data_store.imp().arrivals_list.replace(Some(arrivals_list));
+    arrivals::reveal_arrivals(window, arrivals::arrivals_toggle(window));
+    data_store.imp().minimap.replace(Some(minimap));
+    zones::draw_zones(&desktop, &memo_folder.zones);
+    annotations::draw_annotations(&desktop, &memo_folder.annotations);
+    connectors::draw_connector_layer(&desktop);
+    minimap::show_minimap(window, minimap::minimap_toggle(window));
     // must do it after drawing desktop because it will trigger a save settings and go out of sync b/c done before data_store.desktop is set
     //  (therefore going to the wrong path)
 
     apply_stored_settings(window, &memo_folder);
+    arrivals::refresh_arrivals(window);
     set_title_path(window, path.clone());
+    load_cells(path.clone(), &desktop, memo_folder.shortcuts);
 
-    let watched = gio::File::for_path(path);
+    let watched = gio::File::for_path(path.clone());
     let monitor = watched.monitor_directory(FileMonitorFlags::WATCH_MOVES, None::<&Cancellable>).expect("Fatal: cannot monitor directory");
     monitor.connect_changed(clone!(@weak window => move |_, f, other, event |{
         monitor_folder(f, other, event, &desktop);
@@ -62,8 +114,180 @@ pub(crate) fn draw_folder(path: String, window: &ApplicationWindow) {
     ds.imp().monitor.replace(Some(monitor));
 }
 
+// reads the folder in batches and adds the cells of each batch at their saved positions; new cells are placed last,
+// once all cells are known, so that they do not take the place of a cell not loaded yet
+fn load_cells(path: String, desktop: &Fixed, shortcuts: Vec<MemoShortcut>) {
+    let ds = gtk_wrappers::get_application(desktop);
+    let cancellable = ds.imp().metafolder.borrow().load_cancellable.clone();
+    let desktop = desktop.clone();
+    glib::spawn_future_local(async move {
+        let folder = gio::File::for_path(path.as_str());
+        let enumerate = folder.enumerate_children_future(files::ENTRY_ATTRIBUTES, FileQueryInfoFlags::NOFOLLOW_SYMLINKS, glib::Priority::DEFAULT);
+        let enumerator = match CancellableFuture::new(enumerate, cancellable.clone()).await {
+            Ok(Ok(enumerator)) => enumerator,
+            Ok(Err(err)) => {
+                alert(&desktop, "folder could not be read".to_string(), err.to_string());
+                return;
+            }
+            Err(_) => return,
+        };
+        loop {
+            let batch = enumerator.next_files_future(LOAD_BATCH_SIZE, glib::Priority::DEFAULT);
+            let infos = match CancellableFuture::new(batch, cancellable.clone()).await {
+                Ok(Ok(infos)) => infos,
+                Ok(Err(err)) => {
+                    println!("error reading {}: {}", path, err);
+                    break;
+                }
+                Err(_) => return,
+            };
+            if infos.is_empty() {
+                break;
+            }
+            // the links and launchers of a batch are read together
+            let resolving: Vec<_> = infos.iter()
+                .map(|info| glib::spawn_future_local(files::resolve_dir_item(files::dir_item_from_info(info), path.clone())))
+                .collect();
+            let Some(dir_items) = resolve_all(resolving, &cancellable).await else { return };
+            add_loaded_cells(&desktop, dir_items);
+        }
+        // shortcuts come last: a file of the folder with the same name keeps it
+        let resolving: Vec<_> = shortcuts.into_iter().map(|s| glib::spawn_future_local(files::get_shortcut_info(s))).collect();
+        let Some(dir_items) = resolve_all(resolving, &cancellable).await else { return };
+        add_loaded_cells(&desktop, dir_items);
+        finish_loading(&desktop);
+    });
+}
+
+// None once the loading is cancelled
+async fn resolve_all(resolving: Vec<glib::JoinHandle<DirItem>>, cancellable: &Cancellable) -> Option<Vec<DirItem>> {
+    let mut dir_items = Vec::new();
+    for handle in resolving {
+        match CancellableFuture::new(handle, cancellable.clone()).await {
+            Ok(Ok(dir_item)) => dir_items.push(dir_item),
+            Ok(Err(err)) => println!("error reading a file: {}", err),
+            Err(_) => return None,
+        }
+    }
+    Some(dir_items)
+}
+
+fn add_loaded_cells(desktop: &Fixed, dir_items: Vec<DirItem>) {
+    let ds = gtk_wrappers::get_application(desktop);
+    let mut mf = ds.imp().metafolder.borrow_mut();
+    for mut dir_item in dir_items {
+        let mut name = dir_item.name.clone();
+        // already added by the folder monitor
+        if mf.dir_items.contains_key(&name) && dir_item.target.is_none() {
+            continue;
+        }
+        // a file of the folder took the name of the shortcut while metafolder was not looking
+        if mf.dir_items.contains_key(&name) {
+            let (new_name, result) = mf.move_shortcut_aside(name.as_str());
+            if let Some(err) = result {
+                alert(desktop, "folder settings could not be saved".to_string(), err.to_string());
+            }
+            name = new_name;
+            dir_item.name = name.clone();
+        }
+        // shortcuts are always shown
+        if dir_item.target.is_none() && !mf.is_shown(&dir_item) {
+            mf.unshown.insert(name);
+            continue;
+        }
+        // placed cells get their widget once in view
+        if mf.positions.borrow().contains_key(&name) {
+            mf.dir_items.insert(name, dir_item);
+        } else {
+            mf.add_cell(desktop, dir_item);
+        }
+    }
+    mf.apply_view(desktop);
+}
+
+fn finish_loading(desktop: &Fixed) {
+    let ds = gtk_wrappers::get_application(desktop);
+    let mf = ds.imp().metafolder.borrow();
+    mf.forget_missing_files();
+    drop(mf);
+    place_new_cells(desktop);
+    arrivals::refresh_arrivals(desktop);
+    if is_auto_fit_enabled(desktop) {
+        ds.imp().metafolder.borrow_mut().fit_to_window(desktop);
+    }
+}
+
+// keeps anchored cells at their distance from the window edges and refits the desktop while auto fit is on
+// an empty child filling the overlay, whose resize signal follows the size of the scrolled window
+fn add_resize_handler(overlay: &Overlay) {
+    let tracker = DrawingArea::new();
+    tracker.set_can_target(false);
+    tracker.connect_resize(|tracker, _, _| {
+        // applied once the scrolled window has its new size
+        glib::idle_add_local_once(clone!(@weak tracker => move || {
+            // a folder drawn since
+            if tracker.root().is_none() {
+                return;
+            }
+            let ds = gtk_wrappers::get_application(&tracker);
+            if is_auto_fit_enabled(&tracker) {
+                ds.imp().metafolder.borrow_mut().fit_to_window(&tracker);
+            } else {
+                ds.imp().metafolder.borrow().apply_view(&gtk_wrappers::get_desktop(&tracker));
+            }
+        }));
+    });
+    overlay.add_overlay(&tracker);
+}
+
+// anchored cells keep to the window edges while the desktop scrolls
+fn add_anchor_handler(scrolled_window: &ScrolledWindow) {
+    for adjustment in [scrolled_window.hadjustment(), scrolled_window.vadjustment()] {
+        adjustment.connect_value_changed(clone!(@weak scrolled_window => move |_| {
+            if scrolled_window.root().is_none() {
+                return;
+            }
+            let ds = gtk_wrappers::get_application(&scrolled_window);
+            let Ok(mf) = ds.imp().metafolder.try_borrow() else { return };
+            mf.follow_anchors(&gtk_wrappers::get_desktop(&scrolled_window));
+        }));
+    }
+}
+
+// realizes the cells coming into view and drops those far out of it when the desktop scrolls or the window resizes;
+// changes of the desktop itself ask for it through update_extent
+fn add_virtualization_handler(scrolled_window: &ScrolledWindow) {
+    for adjustment in [scrolled_window.hadjustment(), scrolled_window.vadjustment()] {
+        adjustment.connect_value_changed(clone!(@weak scrolled_window => move |_| {
+            request_virtualize(&scrolled_window);
+        }));
+        adjustment.connect_notify_local(Some("page-size"), clone!(@weak scrolled_window => move |_, _| {
+            request_virtualize(&scrolled_window);
+        }));
+    }
+}
+
+fn request_virtualize(scrolled_window: &ScrolledWindow) {
+    // not shown yet, or a folder drawn since
+    if scrolled_window.root().is_none() {
+        return;
+    }
+    let ds = gtk_wrappers::get_application(scrolled_window);
+    let Ok(mf) = ds.imp().metafolder.try_borrow() else {
+        // busy: asked again once idle
+        glib::idle_add_local_once(clone!(@weak scrolled_window => move || request_virtualize(&scrolled_window)));
+        return;
+    };
+    mf.request_virtualize(&gtk_wrappers::get_desktop(scrolled_window));
+}
+
 fn apply_stored_settings(w: &impl IsA<gtk::Widget>, memo_folder: &MemoFolder) {
     set_drilldown_switch(w, memo_folder.drilldown);
+    set_drop_policy_dropdown(w, memo_folder.drop_policy);
+    set_spacing_scale(w, memo_folder.spacing);
+    set_placement_dropdown(w, memo_folder.placement);
+    set_lock_widgets(w, memo_folder.locked);
+    set_show_hidden_button(w, memo_folder.show_hidden);
     set_bgcolor_button_color(w, memo_folder.background_color.clone());
     if memo_folder.zoom {
         let ds = gtk_wrappers::get_application(w);
@@ -73,7 +297,7 @@ fn apply_stored_settings(w: &impl IsA<gtk::Widget>, memo_folder: &MemoFolder) {
     }
     if memo_folder.cell_size != 0 {
         let ds = gtk_wrappers::get_application(w);
-        ds.imp().metafolder.borrow().change_cell_size(memo_folder.cell_size, false);
+        ds.imp().metafolder.borrow().change_cell_size(memo_folder.cell_size, false, w);
         set_cell_size_scale(ds, memo_folder.cell_size);
     }
     if memo_folder.font_color != "" {
@@ -85,12 +309,12 @@ fn apply_stored_settings(w: &impl IsA<gtk::Widget>, memo_folder: &MemoFolder) {
     }
     if memo_folder.font_size != "" {
         let ds = gtk_wrappers::get_application(w);
-        ds.imp().metafolder.borrow().change_font_size(memo_folder.font_size.clone(), false);
+        ds.imp().metafolder.borrow().change_font_size(memo_folder.font_size.clone(), false, w);
         set_font_size_scale(w, memo_folder.font_size.clone());
     }
     if !memo_folder.font_bold.is_none() && !memo_folder.font_bold.unwrap() {
         let ds = gtk_wrappers::get_application(w);
-        ds.imp().metafolder.borrow().change_bold(memo_folder.font_bold.unwrap(), false);
+        ds.imp().metafolder.borrow().change_bold(memo_folder.font_bold.unwrap(), false, w);
         set_font_bold_switch(ds, false)
     }
 }
@@ -101,25 +325,51 @@ fn drop_action(dnd_msg: &Value, desktop: &Fixed, x: f64, y: f64) -> bool {
     match gtk_wrappers::extract_from_variant(dnd_msg) {
         Ok(dnd_info) => {
             let name = dnd_info.name;
-            if gtk_wrappers::is_something_underneath(name.clone(), &desktop, x - dnd_info.grabbed_x, y - dnd_info.grabbed_y, dnd_info.w, dnd_info.h) {
+            let mf = data_store.imp().metafolder.borrow();
+            // the grab point is in cell coordinates, the cell may be scaled on the desktop
+            let new_x = x - dnd_info.grabbed_x * mf.view_scale;
+            let new_y = y - dnd_info.grabbed_y * mf.view_scale;
+            if mf.locked {
                 return false;
             }
+            let outcome = mf.drop_policy.resolve(mf.cells_underneath(name.as_str(), &Bounds::new(new_x, new_y, dnd_info.w, dnd_info.h)));
+            if let DropOutcome::Blocked(occupants) = &outcome {
+                for occupant in occupants {
+                    if let Some(cell) = mf.cell_map.get(occupant) {
+                        flash_cell(cell, "icon_blocked");
+                    }
+                }
+                return false;
+            }
+            drop(mf);
             let mut mf = data_store.imp().metafolder.borrow_mut();
             if mf.is_cell_newly_added(name.clone()) {
                 mf.clear_added_flag(name.clone());
             }
-            drop(mf);
-            let mf = data_store.imp().metafolder.borrow();
-            if let Some(err) = mf.scan_positions_and_save_settings(&desktop, name.as_str(), x - dnd_info.grabbed_x, y - dnd_info.grabbed_y) {
+            // moved first so that pushed cells are not put back under it and the offsets of an anchored cell are up to date
+            mf.move_cell(desktop, name.as_str(), new_x, new_y);
+            match outcome {
+                DropOutcome::Swap(occupant) => mf.move_cell(desktop, occupant.as_str(), dnd_info.pos_x, dnd_info.pos_y),
+                DropOutcome::Push(occupants) => {
+                    for occupant in occupants {
+                        let (free_x, free_y) = mf.free_spot_near(occupant.as_str());
+                        mf.move_cell(desktop, occupant.as_str(), free_x, free_y);
+                    }
+                }
+                DropOutcome::Move | DropOutcome::Blocked(_) => {}
+            }
+            if let Some(err) = mf.scan_positions_and_save_settings(name.as_str(), new_x, new_y) {
                 let alert = gtk::AlertDialog::builder().modal(true).detail(err.to_string()).message("folder settings could not be saved").build();
                 let root = <Fixed as AsRef<Fixed>>::as_ref(&desktop).root().unwrap();
                 let app_window: ApplicationWindow = root.downcast().unwrap();
                 alert.show(Some(&app_window));
                 return false;
             }
-            let cell = mf.get_cell(name.clone());
-            desktop.move_(cell, x - dnd_info.grabbed_x, y - dnd_info.grabbed_y);
-            cell.remove_css_class("icon_added");
+            if let Some(cell) = mf.cell_map.get(&name) {
+                cell.remove_css_class("icon_added");
+            }
+            drop(mf);
+            arrivals::refresh_arrivals(desktop);
             true
         }
         Err(err) => {
@@ -130,91 +380,166 @@ fn drop_action(dnd_msg: &Value, desktop: &Fixed, x: f64, y: f64) -> bool {
 }
 
 fn monitor_folder(f: &File, other: Option<&File>, event: FileMonitorEvent, d: &Fixed) {
-    if f.basename().unwrap().to_str().unwrap() == ".metafolder" ||
-        f.basename().unwrap().to_str().unwrap().starts_with(".") ||
-        f.basename().unwrap().to_str().unwrap().ends_with("tmp") {
+    let name = f.basename().unwrap().to_str().unwrap().to_string();
+    // the settings of the folder are never a cell
+    if name == ".metafolder" {
+        return;
+    }
+    // new rules: the folder is read again with them, once; a created file is followed by ChangesDoneHint when written
+    let renamed_to_rules = other.and_then(|o| o.basename()).is_some_and(|b| b.to_str() == Some(files::IGNORE_FILE));
+    if name == files::IGNORE_FILE || (event == FileMonitorEvent::Renamed && renamed_to_rules) {
+        if matches!(event, FileMonitorEvent::ChangesDoneHint | FileMonitorEvent::Deleted | FileMonitorEvent::MovedIn | FileMonitorEvent::MovedOut | FileMonitorEvent::Renamed) {
+            redraw_folder(d);
+        }
         return;
     }
     match event {
         FileMonitorEvent::Deleted | FileMonitorEvent::MovedOut => {
-            let ds = gtk_wrappers::get_application(d);
-            let name = f.basename().expect("Fatal: no basename");
-            let (icon, _err) = ds.imp().metafolder.borrow_mut().delete_cell(name.to_str().unwrap().to_string());
-            d.remove(&icon);
+            remove_cell(d, name.as_str());
         }
         FileMonitorEvent::Created | FileMonitorEvent::MovedIn => {
-            let full_path_unwrap = f.path().unwrap();
-            let full_path = full_path_unwrap.to_str().unwrap();
-            let file_info = files::get_file_info(full_path.to_string());
-
-            let cell = cell::make_cell(full_path.to_string(), &file_info.unwrap(), ICON_SIZE);
-            let drag_source = cell::make_drag_source(f.basename().unwrap().to_str().unwrap().to_string(), &cell, d);
-            cell.add_controller(drag_source);
-            cell.set_css_classes(&["icon_added"]);
-            drop_icon_on_free_space(d, &cell, ICON_SIZE, INITIAL_DESKTOP_WIDTH);
-            let ds = gtk_wrappers::get_application(d);
-            ds.imp().metafolder.borrow_mut().add_cell(f.basename().unwrap().to_str().unwrap().to_string(), cell);
+            add_new_cell(d, f);
         }
         FileMonitorEvent::Renamed => {
-            let old_name_binding = f.basename().unwrap();
-            let old_name = old_name_binding.to_str().unwrap();
-            let new_name_binding = other.unwrap().basename().unwrap();
-            let new_name = new_name_binding.to_str().unwrap();
+            let Some(other) = other else { return };
+            rename_cell(d, name, other.clone());
+        }
+        FileMonitorEvent::AttributeChanged => {
+            // a launcher made executable or not
+            let ds = gtk_wrappers::get_application(d);
+            let is_launcher = ds.imp().metafolder.borrow().dir_items.get(&name).is_some_and(|dir_item| dir_item.target.is_none() && dir_item.launcher.is_some());
+            if is_launcher {
+                launchers::refresh_trust(d, name, f.path().unwrap().to_str().unwrap().to_string());
+            }
+        }
+        FileMonitorEvent::ChangesDoneHint => {
+            // the thumbnail and the details of a modified file are stale
             let ds = gtk_wrappers::get_application(d);
-            ds.imp().metafolder.borrow_mut().rename_cell(old_name, new_name);
+            let mf = ds.imp().metafolder.borrow();
+            mf.tooltip_details.borrow_mut().remove(&name);
+            if mf.custom_icons.contains_key(&name) {
+                return;
+            }
+            if let (Some(cell), Some(dir_item)) = (mf.cell_map.get(&name), mf.dir_items.get(&name)) {
+                cell::load_thumbnail(cell, mf.get_current_path(), dir_item, &mf.load_cancellable);
+            }
         }
-        _ => { println!("Unhandled file event on {}", f.basename().unwrap().to_str().unwrap()); }
+        _ => { println!("Unhandled file event on {}", name); }
     }
 }
 
-fn draw_icons(path: String, entries: HashSet<files::DirItem>, desktop: &Fixed, desktop_width: i32, icon_size: i32, memo_desktop: &files::MemoFolder) -> (HashMap<String, gtk::Box>, HashSet<String>) {
-    let mut cell_map: HashMap<String, gtk::Box> = HashMap::new();
-    let mut new_entries: HashSet<String> = HashSet::new();
-    let memo_icons = &memo_desktop.icons;
-    let mut max_y = 0;
-    for entry in entries {
-        let name = entry.name.clone();
-        let cell = cell::make_cell(String::from(&path), &entry, icon_size);
-        let drag_source = cell::make_drag_source(name.clone(), &cell, &desktop);
-        cell.add_controller(drag_source);
-        if !memo_icons.contains_key(name.as_str()) {
-            new_entries.insert(name.clone());
-        } else {
-            desktop.put(&cell, memo_icons.get(name.as_str()).unwrap().position_x as f64, memo_icons.get(name.as_str()).unwrap().position_y as f64);
-            if memo_icons.get(name.as_str()).unwrap().position_y > max_y {
-                max_y = memo_icons.get(name.as_str()).unwrap().position_y;
-            }
-        }
-        cell_map.insert(name, cell);
+// the file is read in the background; the folder may have changed meanwhile
+fn add_new_cell(d: &Fixed, f: &File) {
+    let ds = gtk_wrappers::get_application(d);
+    let cancellable = ds.imp().metafolder.borrow().load_cancellable.clone();
+    let path = f.path().unwrap().to_str().unwrap().to_string();
+    let d = d.clone();
+    glib::spawn_future_local(async move {
+        let file_info = files::get_file_info(path).await;
+        // gone already
+        let Some(file_info) = file_info.filter(|_| !cancellable.is_cancelled()) else { return };
+        add_dir_item(&d, file_info);
+    });
+}
+
+fn add_dir_item(d: &Fixed, dir_item: DirItem) {
+    let ds = gtk_wrappers::get_application(d);
+    let name = dir_item.name.clone();
+    if ds.imp().metafolder.borrow().shows_file(name.as_str()) {
+        return;
+    }
+    if !ds.imp().metafolder.borrow().is_shown(&dir_item) {
+        ds.imp().metafolder.borrow_mut().unshown.insert(name);
+        return;
     }
+    move_shortcut_aside(d, name.as_str());
+    ds.imp().metafolder.borrow_mut().add_cell(d, dir_item);
+    ds.imp().metafolder.borrow().place_new_cell(d, name.as_str());
+    arrivals::refresh_arrivals(d);
+}
 
-    let mut r: i32 = 0;
-    let mut c: i32 = max_y;
-    for (name, cell) in &cell_map {
-        c += icon_size + icon_size / 3;
-        if c > desktop_width {
-            c = 0;
-            r += 2 * icon_size;
+// renamed into or out of the hidden and ignored files, it comes or goes
+fn rename_cell(d: &Fixed, name: String, other: File) {
+    let ds = gtk_wrappers::get_application(d);
+    let cancellable = ds.imp().metafolder.borrow().load_cancellable.clone();
+    let d = d.clone();
+    glib::spawn_future_local(async move {
+        let dir_item = files::get_file_info(other.path().unwrap().to_str().unwrap().to_string()).await;
+        if cancellable.is_cancelled() {
+            return;
+        }
+        // gone already under its new name
+        let Some(dir_item) = dir_item else {
+            remove_cell(&d, name.as_str());
+            return;
+        };
+        let new_name = dir_item.name.clone();
+        let ds = gtk_wrappers::get_application(&d);
+        let was_shown = ds.imp().metafolder.borrow().shows_file(name.as_str());
+        let is_shown = ds.imp().metafolder.borrow().is_shown(&dir_item);
+        let mut mf = ds.imp().metafolder.borrow_mut();
+        mf.unshown.remove(&name);
+        if !is_shown {
+            mf.unshown.insert(new_name.clone());
         }
-        if new_entries.contains(name.as_str()) {
-            cell.set_css_classes(&["icon_added"]);
-            desktop.put(cell, c as f64, r as f64);
+        drop(mf);
+        match (was_shown, is_shown) {
+            (true, true) => {
+                move_shortcut_aside(&d, new_name.as_str());
+                ds.imp().metafolder.borrow_mut().rename_cell(name.as_str(), new_name.as_str());
+                arrivals::refresh_arrivals(&d);
+            }
+            (true, false) => remove_cell(&d, name.as_str()),
+            (false, true) => add_dir_item(&d, dir_item),
+            (false, false) => {}
         }
+    });
+}
+
+// files of the folder win over shortcuts of the same name
+fn move_shortcut_aside(d: &Fixed, name: &str) {
+    let ds = gtk_wrappers::get_application(d);
+    if !ds.imp().metafolder.borrow().is_shortcut(name) {
+        return;
+    }
+    let (_, result) = ds.imp().metafolder.borrow_mut().move_shortcut_aside(name);
+    if let Some(err) = result {
+        alert(d, "folder settings could not be saved".to_string(), err.to_string());
     }
+}
 
-    (cell_map, new_entries)
+fn remove_cell(d: &Fixed, name: &str) {
+    let ds = gtk_wrappers::get_application(d);
+    // a hidden or ignored file loses its settings at the next save
+    let mut mf = ds.imp().metafolder.borrow_mut();
+    if mf.unshown.remove(name) {
+        mf.positions.borrow_mut().remove(name);
+    }
+    drop(mf);
+    // not loaded yet, hidden, ignored, or the name of a shortcut
+    if !ds.imp().metafolder.borrow().shows_file(name) {
+        return;
+    }
+    let (icon, _err) = ds.imp().metafolder.borrow_mut().delete_cell(name.to_string());
+    if let Some(icon) = icon {
+        d.remove(&icon);
+    }
+    arrivals::refresh_arrivals(d);
 }
 
-fn drop_icon_on_free_space(d: &Fixed, icon: &gtk::Box, icon_size: i32, desktop_width: i32) {
-    let mut r: i32 = 0;
-    let mut c: i32 = 0;
+// shows the current folder again, e.g. after the rules of what it shows changed; not from within the monitor handler
+pub(crate) fn redraw_folder(w: &impl IsA<gtk::Widget>) {
+    let Some(window) = w.root().and_then(|r| r.downcast::<ApplicationWindow>().ok()) else { return };
+    let ds = gtk_wrappers::get_application(w);
+    let path = ds.imp().metafolder.borrow().get_current_path();
+    glib::idle_add_local_once(move || draw_folder(path, &window));
+}
 
-    while is_something_underneath("".to_string(), d, c as f64, r as f64, icon_size as f64, icon_size as f64) {
-        c += icon_size + icon_size / 3;
-        if c > desktop_width {
-            c = 0;
-            r += 2 * icon_size;
-        }
+fn place_new_cells(desktop: &Fixed) {
+    let ds = gtk_wrappers::get_application(desktop);
+    let mf = ds.imp().metafolder.borrow();
+    mf.reindex(desktop);
+    for name in mf.sorted_added_cells() {
+        mf.place_new_cell(desktop, name.as_str());
     }
-    d.put(icon, c as f64, r as f64);
-}
\ No newline at end of file
+}
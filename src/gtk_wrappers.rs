@@ -1,41 +1,21 @@
 use std::error::Error;
-use gtk::prelude::{RangeExt};
-use gtk::{ApplicationWindow, Fixed, gdk, glib, PickFlags};
+use std::sync::Mutex;
+use std::time::Duration;
+use gtk::prelude::{AdjustmentExt, ButtonExt, RangeExt, ToggleButtonExt};
+use gtk::{ApplicationWindow, Fixed, gdk, glib};
 use gtk::gdk::RGBA;
 use gtk::glib::{Value, Variant};
-use gtk::graphene::Rect;
-use gtk::prelude::{Cast, FixedExt, GtkWindowExt, IsA, ObjectExt, WidgetExt};
+use gtk::graphene::{Point, Rect};
+use gtk::prelude::{Cast, EventControllerExt, FixedExt, GestureDragExt, GtkWindowExt, IsA, WidgetExt};
 use gtk::subclass::prelude::ObjectSubclassIsExt;
 
 use crate::app_with_datastore::AppWithDatastore;
 use crate::cell::DNDInfo;
-use crate::{CELL_SIZES, DEFAULT_BG_COLOR, FONT_SIZES};
+use crate::{CELL_SIZES, DEFAULT_BG_COLOR, FONT_SIZES, INITIAL_DESKTOP_WIDTH};
+use crate::files::{DropPolicy, PlacementStrategy};
+use crate::spatial::Bounds;
 
-pub fn is_something_underneath(name: String, d: &Fixed, x: f64, y: f64, w: f64, h: f64) -> bool {
-    struct Point {
-        x: f64,
-        y: f64,
-    }
-    let points: [Point; 5] = [Point { x, y }, Point { x: x + w, y }, Point { x, y: y + h }, Point { x: x + w, y: y + h }, Point{x: x + w/2f64, y: y+ h/2f64}];
-    for p in points {
-        match d.pick(p.x, p.y, PickFlags::DEFAULT) {
-            None => {
-                println!("Unexpected: desktop.pick == None")
-            }
-            Some(underlying_icon) => {
-                let widget_type = underlying_icon.type_().to_string();
-                if widget_type != "GtkFixed" {
-                    let img_name = underlying_icon.tooltip_text().unwrap();
-                    if name == img_name {
-                        return false;
-                    }
-                    return true;
-                }
-            }
-        }
-    }
-    false
-}
+static TILE_CLASSES: Mutex<Vec<String>> = Mutex::new(Vec::new());
 
 pub fn extract_from_variant(v: &Value) -> Result<DNDInfo, Box<dyn Error>> {
     let variant = v.get::<Variant>()?;
@@ -65,6 +45,17 @@ pub fn get_widget_bounds(container: &Fixed, w: &gtk::Box) -> Rect {
     }
 }
 
+// position from the Fixed layout, size from the natural size Fixed allocates - valid even before the first allocation
+pub fn get_cell_bounds(container: &Fixed, w: &gtk::Box) -> Option<Bounds> {
+    if w.parent().as_ref() != Some(container.upcast_ref::<gtk::Widget>()) {
+        return None;
+    }
+    let transform = container.child_transform(w)?;
+    let (_, natural) = w.preferred_size();
+    let bounds = transform.transform_bounds(&Rect::new(0f32, 0f32, natural.width() as f32, natural.height() as f32));
+    Some(Bounds::new(bounds.x() as f64, bounds.y() as f64, bounds.width() as f64, bounds.height() as f64))
+}
+
 pub fn get_application(w: &impl IsA<gtk::Widget>) -> AppWithDatastore {
     let root = w.root().unwrap();
     let app_window = root.downcast::<ApplicationWindow>().unwrap();
@@ -73,16 +64,54 @@ pub fn get_application(w: &impl IsA<gtk::Widget>) -> AppWithDatastore {
     ds
 }
 
-pub fn get_desktop(sw: &impl IsA<gtk::Widget>) -> Fixed {
-    let root = sw.root().unwrap();
+pub fn get_scrolled_window(w: &impl IsA<gtk::Widget>) -> gtk::ScrolledWindow {
+    let root = w.root().unwrap();
     let app_window = root.downcast::<gtk::ApplicationWindow>().unwrap();
-    let scrolled_window = app_window.child().unwrap();
+    // the window content is the scrolled desktop, overlaid by the minimap, followed by the arrivals tray
+    let content = app_window.child().unwrap();
+    let overlay = content.first_child().unwrap().downcast::<gtk::Overlay>().unwrap();
+    overlay.child().unwrap().downcast::<gtk::ScrolledWindow>().unwrap()
+}
+
+// a point of a drag in desktop coordinates, right even when the dragged widget moves along
+pub fn drag_point_on_desktop(gesture: &gtk::GestureDrag, offset_x: f64, offset_y: f64) -> (f64, f64) {
+    let (start_x, start_y) = gesture.start_point().unwrap_or((0f64, 0f64));
+    let widget = gesture.widget();
+    let point = Point::new((start_x + offset_x) as f32, (start_y + offset_y) as f32);
+    let on_desktop = widget.compute_point(&get_desktop(&widget), &point).unwrap_or(point);
+    (on_desktop.x() as f64, on_desktop.y() as f64)
+}
+
+pub fn get_desktop(sw: &impl IsA<gtk::Widget>) -> Fixed {
+    let scrolled_window = get_scrolled_window(sw);
     let viewport = scrolled_window.first_child().unwrap();
     let fixed_widget = viewport.first_child().unwrap();
     let fixed = fixed_widget.downcast::<gtk::Fixed>().unwrap();
     fixed
 }
 
+pub fn get_visible_width(w: &impl IsA<gtk::Widget>) -> i32 {
+    let root = w.root().unwrap();
+    let app_window = root.downcast::<gtk::ApplicationWindow>().unwrap();
+    if app_window.width() > 0 {
+        return app_window.width();
+    }
+    // not allocated yet
+    let (default_width, _) = app_window.default_size();
+    if default_width > 0 {
+        return default_width;
+    }
+    INITIAL_DESKTOP_WIDTH
+}
+
+// the part of the desktop currently scrolled into view
+pub fn get_visible_area(w: &impl IsA<gtk::Widget>) -> Bounds {
+    let scrolled_window = get_scrolled_window(w);
+    let h = scrolled_window.hadjustment();
+    let v = scrolled_window.vadjustment();
+    Bounds::new(h.value(), v.value(), h.page_size(), v.page_size())
+}
+
 pub fn set_cell_size_scale(w: AppWithDatastore, cell_size: i32) {
     //let app = get_application(w);
     let cell_size_scale_binding = w.imp().cell_size_scale.borrow();
@@ -142,6 +171,35 @@ pub fn set_window_background(rgba: String) {
     );
 }
 
+// css class giving a cell a tile of the colour; the rule of each colour is added to the display once
+pub fn tile_class(color: &str) -> String {
+    let rgba = RGBA::parse(color).unwrap_or(RGBA::TRANSPARENT);
+    let class = format!("tile_{:02x}{:02x}{:02x}{:02x}", channel(rgba.red()), channel(rgba.green()), channel(rgba.blue()), channel(rgba.alpha()));
+    let mut installed = TILE_CLASSES.lock().unwrap();
+    if !installed.contains(&class) {
+        let css = format!(".{} {{background-color: {}; border-radius: 7px;}}", class, rgba);
+        let provider = gtk::CssProvider::new();
+        provider.load_from_bytes(&glib::Bytes::from(css.as_bytes()));
+        // beneath the found and added highlights
+        gtk::style_context_add_provider_for_display(
+            &gdk::Display::default().expect("Could not connect to a display."),
+            &provider,
+            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION - 1,
+        );
+        installed.push(class.clone());
+    }
+    class
+}
+
+fn channel(value: f32) -> u8 {
+    (value * 255f32).round() as u8
+}
+
+// hex colour as stored in the folder settings, alpha left out
+pub fn hex_color(rgba: &RGBA) -> String {
+    format!("#{:02X}{:02X}{:02X}", channel(rgba.red()), channel(rgba.green()), channel(rgba.blue()))
+}
+
 pub fn alert(w: &impl IsA<gtk::Widget>, msg: String, err: String) {
     let alert = gtk::AlertDialog::builder().modal(true).detail(err).message(msg).build();
     let root = w.root().unwrap();
@@ -188,9 +246,74 @@ pub fn set_zoom_widgets(w: &impl IsA<gtk::Widget>, zoom: bool, zoom_x: i32, zoom
     zoom_y_scale.set_value(zoom_y as f64);
 }
 
+pub fn is_visual_zoom_enabled(w: &impl IsA<gtk::Widget>) -> bool {
+    let app = get_application(w);
+    let binding = app.imp().visual_zoom_switch.borrow();
+    binding.as_ref().map(|sw| sw.is_active()).unwrap_or(false)
+}
+
+pub fn set_visual_zoom_switch(w: &impl IsA<gtk::Widget>, enabled: bool) {
+    let app = get_application(w);
+    let binding = app.imp().visual_zoom_switch.borrow();
+    if let Some(sw) = binding.as_ref() {
+        sw.set_active(enabled);
+    }
+}
+
+pub fn is_auto_fit_enabled(w: &impl IsA<gtk::Widget>) -> bool {
+    let app = get_application(w);
+    let binding = app.imp().auto_fit_switch.borrow();
+    binding.as_ref().map(|sw| sw.is_active()).unwrap_or(false)
+}
+
 pub fn set_title_path(w: &impl IsA<gtk::Widget>, path: String) {
     let app = get_application(w);
     let binding = app.imp().path_label.borrow();
     let bg = binding.as_ref();
     bg.unwrap().set_label(&path);
-}
\ No newline at end of file
+}
+
+pub fn set_spacing_scale(w: &impl IsA<gtk::Widget>, spacing: i32) {
+    let app = get_application(w);
+    let binding = app.imp().spacing_scale.borrow();
+    let sp = binding.as_ref();
+    sp.unwrap().set_value(spacing as f64);
+}
+
+pub fn set_drop_policy_dropdown(w: &impl IsA<gtk::Widget>, policy: DropPolicy) {
+    let app = get_application(w);
+    let binding = app.imp().drop_policy_dropdown.borrow();
+    let dd = binding.as_ref();
+    dd.unwrap().set_selected(policy.index());
+}
+
+pub fn set_lock_widgets(w: &impl IsA<gtk::Widget>, locked: bool) {
+    let app = get_application(w);
+    let lock_binding = app.imp().lock_button.borrow();
+    let lock_button = lock_binding.as_ref().unwrap();
+    lock_button.set_active(locked);
+    lock_button.set_icon_name(if locked { "changes-prevent-symbolic" } else { "changes-allow-symbolic" });
+    app.imp().lock_indicator.borrow().as_ref().unwrap().set_visible(locked);
+    app.imp().tap_button.borrow().as_ref().unwrap().set_sensitive(!locked);
+    app.imp().zoom_commit_button.borrow().as_ref().unwrap().set_sensitive(!locked);
+    app.imp().drop_policy_dropdown.borrow().as_ref().unwrap().set_sensitive(!locked);
+}
+
+pub fn set_show_hidden_button(w: &impl IsA<gtk::Widget>, show_hidden: bool) {
+    let app = get_application(w);
+    app.imp().show_hidden_button.borrow().as_ref().unwrap().set_active(show_hidden);
+}
+
+pub fn set_placement_dropdown(w: &impl IsA<gtk::Widget>, placement: PlacementStrategy) {
+    let app = get_application(w);
+    let binding = app.imp().placement_dropdown.borrow();
+    let dd = binding.as_ref();
+    dd.unwrap().set_selected(placement.index());
+}
+
+pub fn flash_cell(cell: &gtk::Box, class: &'static str) {
+    cell.add_css_class(class);
+    glib::timeout_add_local_once(Duration::from_millis(600), glib::clone!(@weak cell => move || {
+        cell.remove_css_class(class);
+    }));
+}
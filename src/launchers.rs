@@ -0,0 +1,136 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use gtk::{ApplicationWindow, Button, Fixed, Label, Popover};
+use gtk::gio;
+use gtk::gio::DesktopAppInfo;
+use gtk::glib;
+use gtk::glib::clone;
+use gtk::Orientation::Vertical;
+use gtk::prelude::{AppInfoExt, BoxExt, ButtonExt, Cast, DisplayExt, FileExt, FileExtManual, PopoverExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::gtk_wrappers;
+use crate::gtk_wrappers::alert;
+
+pub(crate) const LAUNCHER_MIME: &str = "application/x-desktop";
+// kept by gvfs for the user, outside of the folder, as Nautilus does
+const TRUSTED_ATTRIBUTE: &str = "metadata::trusted";
+
+// the launcher in a .desktop file, None when the file is not a valid one; read without blocking
+pub(crate) async fn read_launcher(path: &str) -> Option<DesktopAppInfo> {
+    let (content, _) = gio::File::for_path(path).load_contents_future().await.ok()?;
+    let key_file = glib::KeyFile::new();
+    key_file.load_from_bytes(&glib::Bytes::from(content.as_ref()), glib::KeyFileFlags::NONE).ok()?;
+    DesktopAppInfo::from_keyfile(&key_file)
+}
+
+// like in GNOME, a launcher runs once executable and marked trusted by the user; read without blocking
+pub(crate) async fn is_trusted(path: &str) -> bool {
+    let attributes = format!("{},{}", gio::FILE_ATTRIBUTE_ACCESS_CAN_EXECUTE, TRUSTED_ATTRIBUTE);
+    gio::File::for_path(path).query_info_future(attributes.as_str(), gio::FileQueryInfoFlags::NONE, glib::Priority::DEFAULT).await
+        .map(|info| info.boolean(gio::FILE_ATTRIBUTE_ACCESS_CAN_EXECUTE) && info.attribute_string(TRUSTED_ATTRIBUTE).as_deref() == Some("true"))
+        .unwrap_or(false)
+}
+
+// the executable bit of a launcher changed outside of metafolder
+pub(crate) fn refresh_trust(desktop: &Fixed, name: String, path: String) {
+    let desktop = desktop.clone();
+    glib::spawn_future_local(async move {
+        let trusted = is_trusted(path.as_str()).await;
+        // another folder was drawn meanwhile
+        if desktop.root().is_none() {
+            return;
+        }
+        let ds = gtk_wrappers::get_application(&desktop);
+        let mut mf = ds.imp().metafolder.borrow_mut();
+        if let Some(dir_item) = mf.dir_items.get_mut(&name) {
+            dir_item.trusted = trusted;
+        }
+    });
+}
+
+// runs the launcher of the cell, once the user allowed it as in GNOME
+pub(crate) fn activate(cell: &gtk::Box, path: String) {
+    let name = cell.widget_name().to_string();
+    let ds = gtk_wrappers::get_application(cell);
+    let launcher = ds.imp().metafolder.borrow().dir_items.get(&name).and_then(|d| Some((d.launcher.clone()?, d.trusted)));
+    let Some((app_info, trusted)) = launcher else {
+        alert(cell, "cannot launch".to_string(), format!("{} is not a valid launcher", name));
+        return;
+    };
+    if trusted {
+        launch(cell, &app_info);
+        return;
+    }
+    let dialog = gtk::AlertDialog::builder()
+        .modal(true)
+        .message(format!("launch {}?", app_info.name()))
+        .detail(format!("{} is not trusted yet. Only allow launchers you know: they can run any command.", name))
+        .buttons(["Cancel", "Allow launching"])
+        .cancel_button(0)
+        .default_button(0)
+        .build();
+    let window = cell.root().and_then(|r| r.downcast::<ApplicationWindow>().ok());
+    dialog.choose(window.as_ref(), None::<&gio::Cancellable>, clone!(@weak cell => move |choice| {
+        if choice != Ok(1) {
+            return;
+        }
+        if set_trusted(&cell, name.as_str(), path.as_str(), true) {
+            launch(&cell, &app_info);
+        }
+    }));
+}
+
+fn launch(w: &impl gtk::prelude::IsA<gtk::Widget>, app_info: &DesktopAppInfo) {
+    let context = w.display().app_launch_context();
+    if let Err(err) = app_info.launch(&[], Some(&context)) {
+        alert(w, format!("{} could not be launched", app_info.name()), err.to_string());
+    }
+}
+
+// allowing also makes the launcher executable; false when that failed
+fn set_trusted(w: &impl gtk::prelude::IsA<gtk::Widget>, name: &str, path: &str, trusted: bool) -> bool {
+    if trusted {
+        if let Err(err) = make_executable(path) {
+            alert(w, "launcher could not be made executable".to_string(), err.to_string());
+            return false;
+        }
+    }
+    let value = if trusted { "true" } else { "false" };
+    if let Err(err) = gio::File::for_path(path).set_attribute_string(TRUSTED_ATTRIBUTE, value, gio::FileQueryInfoFlags::NONE, gio::Cancellable::NONE) {
+        alert(w, "launcher trust could not be saved".to_string(), err.to_string());
+        return false;
+    }
+    let ds = gtk_wrappers::get_application(w);
+    if let Some(dir_item) = ds.imp().metafolder.borrow_mut().dir_items.get_mut(name) {
+        dir_item.trusted = trusted;
+    }
+    true
+}
+
+// executable by those who can read it
+fn make_executable(path: &str) -> std::io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    let mode = permissions.mode();
+    permissions.set_mode(mode | (mode & 0o444) >> 2);
+    fs::set_permissions(path, permissions)
+}
+
+// the launcher section of the cell menu, only for the cells of launchers
+pub(crate) fn make_launcher_section(name: String, path: String, popover: &Popover) -> gtk::Box {
+    let section = gtk::Box::builder().orientation(Vertical).spacing(5).build();
+    let ds = gtk_wrappers::get_application(popover);
+    let trusted = ds.imp().metafolder.borrow().dir_items.get(&name).is_some_and(|d| d.trusted);
+    let label = if trusted { "disallow launching" } else { "allow launching" };
+    let toggle = Button::builder().label(label).build();
+    toggle.connect_clicked(clone!(@weak popover => move |b| {
+        set_trusted(b, name.as_str(), path.as_str(), !trusted);
+        popover.popdown();
+    }));
+    section.append(&toggle);
+    if !trusted {
+        section.append(&Label::new(Some("double click asks before launching")));
+    }
+    section
+}
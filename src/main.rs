@@ -19,6 +19,21 @@ mod zoom;
 mod cell_editor;
 mod preset;
 mod find;
+mod spatial;
+mod placement;
+mod arrivals;
+mod cell_menu;
+mod canvas;
+mod minimap;
+mod viewpoints;
+mod zones;
+mod annotations;
+mod connectors;
+mod shortcuts;
+mod thumbnails;
+mod tags;
+mod launchers;
+mod tooltips;
 
 const APP_ID: &str = "metafolder";
 const DRAG_ACTION: DragAction = DragAction::MOVE;
@@ -26,9 +41,13 @@ const ICON_SIZE: i32 = 60;
 const INITIAL_DESKTOP_WIDTH: i32 = 1024;
 const DROP_TYPE: Type = Type::VARIANT;
 const DEFAULT_BG_COLOR: &str = "rgba(170, 170, 170, 1)";
-const CLASSES: &str = " .icon_added {background-color: rgba(214, 39, 39, 0.35);} .icon_found {background-color: rgba(250, 255, 0, 0.60);} .folder-zoomed {background-image: none; background-color: rgba(245, 241, 39, 0.8);} .folder-unzoomed {background-image: none;}";
+const CLASSES: &str = " .icon_added {background-color: rgba(214, 39, 39, 0.35);} .icon_found {background-color: rgba(250, 255, 0, 0.60);} .icon_blocked {background-color: rgba(39, 94, 214, 0.45);} .broken_link {opacity: 0.5;} .folder-zoomed {background-image: none; background-color: rgba(245, 241, 39, 0.8);} .folder-unzoomed {background-image: none;}";
 static CELL_SIZES: &'static [i32] = &[40, 60, 80];
 static FONT_SIZES: &'static [&str] = &["xx-small", "x-small", "small", "medium", "large", "x-large", "xx-large"];
+// must coincide with files::DropPolicy::from_index
+static DROP_POLICIES: &[&str] = &["reject", "swap", "push"];
+// must coincide with files::PlacementStrategy::from_index
+static PLACEMENTS: &[&str] = &["first free spot", "window width", "inbox", "next to same type", "end, by name", "end, by date", "into matching zone"];
 
 fn main() -> glib::ExitCode {
     let args: Vec<String> = env::args().collect();
@@ -46,6 +65,9 @@ fn main() -> glib::ExitCode {
 fn build_ui(app: &AppWithDatastore) {
     let window = ApplicationWindow::builder().application(app).title("metafolder").build();
     window.set_titlebar(Some(&make_header_bar(&window)));
+    canvas::add_space_tracking(&window);
+    viewpoints::add_viewpoint_shortcuts(&window);
+    connectors::add_linking_escape(&window);
     window.set_default_size(1024, 768);
     window.maximize();
 
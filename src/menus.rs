@@ -1,4 +1,4 @@
-use gtk::{Align, Button, Label, MenuButton, Orientation, Switch};
+use gtk::{Align, Button, DropDown, Label, MenuButton, Orientation, Switch, ToggleButton};
 use gtk::{ApplicationWindow, ColorDialog, ColorDialogButton, HeaderBar};
 use gtk::gdk::RGBA;
 use gtk::glib::Propagation;
@@ -6,15 +6,17 @@ use gtk::glib::Propagation;
 use crate::glib::clone;
 use gtk::glib;
 
-use gtk::prelude::{BoxExt, ButtonExt, Cast, PopoverExt, WidgetExt};
+use gtk::prelude::{BoxExt, ButtonExt, Cast, PopoverExt, ToggleButtonExt, WidgetExt};
 use gtk::subclass::prelude::ObjectSubclassIsExt;
 
-use crate::{DEFAULT_BG_COLOR, files, gtk_wrappers, zoom};
+use crate::{annotations, arrivals, canvas, DEFAULT_BG_COLOR, DROP_POLICIES, files, folder, gtk_wrappers, minimap, shortcuts, viewpoints, zoom, zones};
+use crate::files::DropPolicy;
 use crate::folder::draw_folder;
 use crate::gtk_wrappers::{alert};
 use crate::cell_editor::make_cell_formatter;
 use crate::find::make_find;
 use crate::preset::make_presets;
+use crate::placement::make_placement;
 
 pub(crate) fn make_header_bar(app_window: &ApplicationWindow) -> HeaderBar {
     let bar = HeaderBar::new();
@@ -36,7 +38,7 @@ pub(crate) fn make_header_bar(app_window: &ApplicationWindow) -> HeaderBar {
     });
     bar.pack_start(&drilldown_switch);
 
-    let (zoom_popover, zoom_x_scale, zoom_y_scale) = zoom::make_zoom();
+    let (zoom_popover, zoom_x_scale, zoom_y_scale, zoom_commit_button, visual_zoom_switch, auto_fit_switch) = zoom::make_zoom();
     let zoom_button = gtk::MenuButton::builder().icon_name("folder").popover(&zoom_popover).build();
     zoom_button.set_tooltip_text(Some("zoom folder in or out"));
     bar.pack_start(&zoom_button);
@@ -66,7 +68,7 @@ pub(crate) fn make_header_bar(app_window: &ApplicationWindow) -> HeaderBar {
     });
     bar.pack_start(&text_color_button);
 
-    let (cell_size_popover, text_scale, bold_switch, cell_size_scale) = make_cell_formatter();
+    let (cell_size_popover, text_scale, bold_switch, cell_size_scale, spacing_scale) = make_cell_formatter();
     let cell_size_button = MenuButton::builder().label("a").popover(&cell_size_popover).build();
     cell_size_button.set_tooltip_text(Some("Edit cells"));
     bar.pack_start(&cell_size_button);
@@ -75,14 +77,76 @@ pub(crate) fn make_header_bar(app_window: &ApplicationWindow) -> HeaderBar {
     tap_button.set_tooltip_text(Some("tap icons to alignment"));
     tap_button.connect_clicked(|b| {
         let ds = gtk_wrappers::get_application(b);
-        ds.imp().metafolder.borrow().tap(b);
+        ds.imp().metafolder.borrow_mut().tap(b);
     });
 
     bar.pack_start(&tap_button);
+
+    let lock_button = ToggleButton::builder().icon_name("changes-allow-symbolic").build();
+    lock_button.set_tooltip_text(Some("lock the layout so icons cannot be moved"));
+    lock_button.connect_toggled(|b| {
+        lock_action(b);
+    });
+    bar.pack_start(&lock_button);
+
+    let show_hidden_button = ToggleButton::builder().icon_name("view-reveal-symbolic").build();
+    show_hidden_button.set_tooltip_text(Some("show hidden files - files matching .metafolderignore stay out"));
+    show_hidden_button.connect_toggled(|b| {
+        show_hidden_action(b);
+    });
+    bar.pack_start(&show_hidden_button);
+
+    let drop_policy_dropdown = DropDown::from_strings(DROP_POLICIES);
+    drop_policy_dropdown.set_tooltip_text(Some("what to do when an icon is dropped on another icon"));
+    drop_policy_dropdown.connect_selected_notify(|dd| {
+        drop_policy_action(dd);
+    });
+    bar.pack_start(&drop_policy_dropdown);
+
+    let (placement_popover, placement_dropdown) = make_placement();
+    let placement_button = MenuButton::builder().icon_name("document-new").popover(&placement_popover).build();
+    placement_button.set_tooltip_text(Some("placement of new files"));
+    bar.pack_start(&placement_button);
+    let canvas_button = MenuButton::builder().icon_name("view-fullscreen-symbolic").popover(&canvas::make_canvas_menu()).build();
+    canvas_button.set_tooltip_text(Some("show everything or make room around the icons"));
+    bar.pack_start(&canvas_button);
+
+    let zone_button = Button::builder().icon_name("insert-object-symbolic").build();
+    zone_button.set_tooltip_text(Some("add a zone - right click a zone to edit it"));
+    zone_button.connect_clicked(|b| {
+        zones::add_zone(b);
+    });
+    bar.pack_start(&zone_button);
+
+    let annotations_button = MenuButton::builder().icon_name("insert-text-symbolic").popover(&annotations::make_annotations_menu()).build();
+    annotations_button.set_tooltip_text(Some("add text, notes, arrows and lines"));
+    bar.pack_start(&annotations_button);
+
+    let shortcuts_button = MenuButton::builder().icon_name("insert-link-symbolic").popover(&shortcuts::make_shortcuts_menu()).build();
+    shortcuts_button.set_tooltip_text(Some("add a shortcut to a file, folder or URL outside the folder"));
+    bar.pack_start(&shortcuts_button);
+
+    let minimap_button = ToggleButton::builder().icon_name("view-grid-symbolic").build();
+    minimap_button.set_tooltip_text(Some("show or hide the minimap"));
+    minimap_button.connect_toggled(|b| {
+        minimap::show_minimap(b, b.is_active());
+    });
+    bar.pack_start(&minimap_button);
+
+    let viewpoints_button = MenuButton::builder().icon_name("find-location-symbolic").popover(&viewpoints::make_viewpoints()).build();
+    viewpoints_button.set_tooltip_text(Some("named viewpoints - ctrl+1 to ctrl+9 go to the first nine"));
+    bar.pack_start(&viewpoints_button);
     // let preset_button = MenuButton::builder().icon_name("document-save").popover(&make_presets()).build();
     // preset_button.set_tooltip_text(Some("create and apply presets"));
     // bar.pack_start(&preset_button);
 
+    let arrivals_button = ToggleButton::builder().label("arrivals").build();
+    arrivals_button.set_tooltip_text(Some("show or hide new files not yet placed"));
+    arrivals_button.connect_toggled(|b| {
+        arrivals::reveal_arrivals(b, b.is_active());
+    });
+    bar.pack_end(&arrivals_button);
+
      let (search_box, search_results) = make_find();
     bar.pack_end(&search_box);
     bar.pack_end(&search_results);
@@ -90,8 +154,14 @@ pub(crate) fn make_header_bar(app_window: &ApplicationWindow) -> HeaderBar {
     let app_name_pango = String::from("<span font_weight =\"bold\">metafolder</span>");
     let app_name_label = Label::builder().use_markup(true).label(app_name_pango.as_str()).build();
     let path_label = Label::new(Some(""));
+    let lock_indicator = gtk::Image::from_icon_name("changes-prevent-symbolic");
+    lock_indicator.set_tooltip_text(Some("layout is locked"));
+    lock_indicator.set_visible(false);
+    let app_name_box = gtk::Box::builder().orientation(Orientation::Horizontal).spacing(5).halign(Align::Center).build();
+    app_name_box.append(&lock_indicator);
+    app_name_box.append(&app_name_label);
     let title_widget = gtk::Box::builder().orientation(Orientation::Vertical).valign(Align::Center).build();
-    title_widget.append(&app_name_label);
+    title_widget.append(&app_name_box);
     title_widget.append(&path_label);
     bar.set_title_widget(Some(&title_widget));
 
@@ -106,6 +176,18 @@ pub(crate) fn make_header_bar(app_window: &ApplicationWindow) -> HeaderBar {
     ds.imp().text_size_scale.replace(Some(text_scale));
     ds.imp().font_bold_switch.replace(Some(bold_switch));
     ds.imp().cell_size_scale.replace(Some(cell_size_scale));
+    ds.imp().spacing_scale.replace(Some(spacing_scale));
+    ds.imp().drop_policy_dropdown.replace(Some(drop_policy_dropdown));
+    ds.imp().placement_dropdown.replace(Some(placement_dropdown));
+    ds.imp().arrivals_button.replace(Some(arrivals_button));
+    ds.imp().minimap_button.replace(Some(minimap_button));
+    ds.imp().lock_button.replace(Some(lock_button));
+    ds.imp().lock_indicator.replace(Some(lock_indicator));
+    ds.imp().show_hidden_button.replace(Some(show_hidden_button));
+    ds.imp().tap_button.replace(Some(tap_button));
+    ds.imp().zoom_commit_button.replace(Some(zoom_commit_button));
+    ds.imp().visual_zoom_switch.replace(Some(visual_zoom_switch));
+    ds.imp().auto_fit_switch.replace(Some(auto_fit_switch));
 
     bar
 }
@@ -140,6 +222,38 @@ fn drilldown_action(sw: &Switch, state: bool) -> Propagation {
     x
 }
 
+fn lock_action(b: &ToggleButton) {
+    let ds = gtk_wrappers::get_application(b);
+    let locked = b.is_active();
+    let result = ds.imp().metafolder.borrow_mut().set_locked(locked);
+    if let Some(err) = result {
+        alert(b, "folder settings could not be saved".to_string(), err.to_string());
+    }
+    gtk_wrappers::set_lock_widgets(b, locked);
+}
+
+fn show_hidden_action(b: &ToggleButton) {
+    let ds = gtk_wrappers::get_application(b);
+    // set from the folder settings
+    if ds.imp().metafolder.borrow().show_hidden == b.is_active() {
+        return;
+    }
+    let result = ds.imp().metafolder.borrow_mut().set_show_hidden(b.is_active());
+    if let Some(err) = result {
+        alert(b, "folder settings could not be saved".to_string(), err.to_string());
+    }
+    folder::redraw_folder(b);
+}
+
+fn drop_policy_action(dd: &DropDown) {
+    let ds = gtk_wrappers::get_application(dd);
+    let policy = DropPolicy::from_index(dd.selected());
+    let result = ds.imp().metafolder.borrow_mut().set_drop_policy(policy);
+    if let Some(err) = result {
+        alert(dd, "folder settings could not be saved".to_string(), err.to_string());
+    }
+}
+
 fn up_button_action(b: &Button) {
     let ds = gtk_wrappers::get_application(b);
     let current_path = ds.imp().metafolder.borrow().get_current_path();
@@ -1,25 +1,115 @@
+use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, HashSet};
 
-use gtk::Fixed;
+use gtk::{Fixed, gio, glib};
+use gtk::glib::clone;
+use gtk::graphene::Point;
+use gtk::gsk::Transform;
 use gtk::prelude::{Cast, FixedExt, IsA, WidgetExt};
 use gtk::subclass::prelude::ObjectSubclassIsExt;
 use ignore::Error;
+use ignore::gitignore::Gitignore;
 use regex::Regex;
 
-use crate::{files, gtk_wrappers};
-use crate::files::{load_settings, MemoIcon};
-use crate::gtk_wrappers::{get_application, get_desktop, get_widget_bounds, set_zoom_widgets};
+use crate::{annotations, cell, files, gtk_wrappers, ICON_SIZE, INITIAL_DESKTOP_WIDTH, minimap, tags, zoom, zones};
+use crate::files::{Anchor, DirItem, DropPolicy, IconStyle, load_settings, MemoAnnotation, MemoConnector, MemoIcon, MemoRect, MemoShortcut, MemoViewpoint, MemoZone, PlacementStrategy};
+use crate::gtk_wrappers::{get_cell_bounds, get_desktop, get_scrolled_window, get_visible_area, get_visible_width, set_zoom_widgets};
+use crate::spatial::{Bounds, SpatialIndex};
+use crate::tooltips::TooltipDetails;
+
+// cells this far out of view, in view pixels, are realized in advance; twice as far they are dropped
+const REALIZE_MARGIN: f64 = 256f64;
+// size of a cell before any has been measured
+const ESTIMATED_CELL_SIZE: (f64, f64) = (ICON_SIZE as f64 * 1.5, ICON_SIZE as f64 * 1.5);
+
+// font settings of the folder, shared by the cell labels and the annotations
+#[derive(Debug, Clone)]
+pub(crate) struct LabelStyle {
+    pub(crate) size: String,
+    pub(crate) color: String,
+    pub(crate) bold: bool,
+}
+
+impl Default for LabelStyle {
+    fn default() -> Self {
+        LabelStyle {
+            size: "small".to_string(),
+            color: "white".to_string(),
+            bold: true,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct MetaFolder {
     pub(crate) background_color: String,
     pub(crate) drilldown: bool,
+    // widgets of the realized cells, those in or near the part of the desktop in view; dir_items holds all cells
     pub(crate) cell_map: HashMap<String, gtk::Box>,
+    // unzoomed positions of the cells; what is on screen is always derived from these
+    pub(crate) positions: RefCell<HashMap<String, (f64, f64)>>,
+    // anchored cells with their distance from the anchored window edges
+    pub(crate) anchors: RefCell<HashMap<String, (Anchor, i32, i32)>>,
+    // image paths or icon names shown instead of the icons of the files
+    pub(crate) custom_icons: HashMap<String, String>,
+    // label and tile looks of single cells, over those of the folder
+    pub(crate) icon_styles: HashMap<String, IconStyle>,
+    // tags of the cells kept in the folder settings; see tags_of for those of the extended attributes
+    pub(crate) tags: HashMap<String, Vec<String>>,
+    pub(crate) xattr_tags: bool,
+    pub(crate) show_hidden: bool,
+    // rules of the .metafolderignore of the folder
+    pub(crate) ignore_rules: Gitignore,
+    // details of the hovered cells, None while being read
+    pub(crate) tooltip_details: RefCell<HashMap<String, Option<TooltipDetails>>>,
+    pub(crate) dir_items: HashMap<String, DirItem>,
+    // files of the folder without a cell, hidden or ignored; their settings are kept
+    pub(crate) unshown: HashSet<String>,
     pub(crate) added_cells: HashSet<String>,
     pub(crate) current_path: String,
     pub(crate) zoom: bool,
     pub(crate) zoom_x: i32,
     pub(crate) zoom_y: i32,
+    // uniform scale of the whole desktop, cells included - only affects the view, never saved
+    pub(crate) view_scale: f64,
+    // room added left of and above the layout, in view pixels - cells may have negative positions until saved
+    pub(crate) origin: Cell<(f64, f64)>,
+    // room added right of and below the layout, in view pixels
+    pub(crate) extra_size: Cell<(f64, f64)>,
+    pub(crate) drop_policy: DropPolicy,
+    // minimum distance kept between cells
+    pub(crate) spacing: i32,
+    pub(crate) spatial_index: RefCell<SpatialIndex>,
+    pub(crate) placement: PlacementStrategy,
+    pub(crate) inbox: Option<MemoRect>,
+    // no cell can be moved while locked
+    pub(crate) locked: bool,
+    pub(crate) viewpoints: Vec<MemoViewpoint>,
+    pub(crate) zones: Vec<MemoZone>,
+    // one widget per zone, in the same order
+    pub(crate) zone_widgets: Vec<gtk::Overlay>,
+    pub(crate) annotations: Vec<MemoAnnotation>,
+    // one widget per annotation, in the same order
+    pub(crate) annotation_widgets: Vec<gtk::Widget>,
+    pub(crate) label_style: RefCell<LabelStyle>,
+    pub(crate) connectors: Vec<MemoConnector>,
+    // drawn over the whole desktop, beneath the cells
+    pub(crate) connector_layer: Option<gtk::DrawingArea>,
+    // first cell of a connector being created, waiting for a click on the second one
+    pub(crate) linking_from: Option<String>,
+    pub(crate) shortcuts: Vec<MemoShortcut>,
+    // cancelled when another folder is shown, stopping the loading of this one and of its thumbnails
+    pub(crate) load_cancellable: gio::Cancellable,
+    // set once the cell size has been changed from its default
+    cell_size: Cell<Option<i32>>,
+    // unscaled size of each cell when last realized, giving the bounds of the cells without a widget
+    sizes: RefCell<HashMap<String, (f64, f64)>>,
+    // size of the cells never realized, that of the last cell measured
+    typical_size: Cell<(f64, f64)>,
+    // set whenever cells may have come into or gone out of view
+    pub(crate) needs_virtualize: Cell<bool>,
+    // a dragged cell keeps its widget even when scrolled out of view
+    pub(crate) dragged: RefCell<Option<String>>,
 
     font_size_replacer: Regex,
     font_color_replacer: Regex,
@@ -41,11 +131,47 @@ impl MetaFolder {
             background_color: "".to_string(),
             drilldown: false,
             cell_map: Default::default(),
+            positions: Default::default(),
+            anchors: Default::default(),
+            custom_icons: Default::default(),
+            icon_styles: Default::default(),
+            tags: Default::default(),
+            xattr_tags: false,
+            show_hidden: false,
+            ignore_rules: Gitignore::empty(),
+            tooltip_details: Default::default(),
+            dir_items: Default::default(),
+            unshown: Default::default(),
             added_cells: Default::default(),
             current_path: "".to_string(),
             zoom: false,
             zoom_x: 0,
             zoom_y: 0,
+            view_scale: 1f64,
+            origin: Cell::new((0f64, 0f64)),
+            extra_size: Cell::new((0f64, 0f64)),
+            drop_policy: DropPolicy::Reject,
+            spacing: 0,
+            spatial_index: RefCell::new(SpatialIndex::default()),
+            placement: PlacementStrategy::FirstFree,
+            inbox: None,
+            locked: false,
+            viewpoints: Vec::new(),
+            zones: Vec::new(),
+            zone_widgets: Vec::new(),
+            annotations: Vec::new(),
+            annotation_widgets: Vec::new(),
+            label_style: RefCell::new(LabelStyle::default()),
+            connectors: Vec::new(),
+            connector_layer: None,
+            linking_from: None,
+            shortcuts: Vec::new(),
+            load_cancellable: gio::Cancellable::new(),
+            cell_size: Cell::new(None),
+            sizes: Default::default(),
+            typical_size: Cell::new(ESTIMATED_CELL_SIZE),
+            needs_virtualize: Cell::new(false),
+            dragged: RefCell::new(None),
             font_size_replacer: Regex::new("font_size=\"[^\"]+\"").unwrap(),
             font_color_replacer: Regex::new("color=\"[^\"]+\"").unwrap(),
             font_weight_replacer: Regex::new("font_weight=\"[^\"]+\"").unwrap(),
@@ -55,17 +181,18 @@ impl MetaFolder {
         m
     }
 
-    pub(crate) fn tap(&self, w: &impl IsA<gtk::Widget>) {
+    pub(crate) fn tap(&mut self, w: &impl IsA<gtk::Widget>) {
+        if self.locked {
+            return;
+        }
         let desktop = get_desktop(w);
 
-        for (_, gbox) in &self.cell_map {
-            let current_pos = get_widget_bounds(&desktop, gbox);
-
-            let new_x = current_pos.x() - ((current_pos.x() as i32) % 5) as f32;
-            let new_y = current_pos.y() - ((current_pos.y() as i32) % 5) as f32;
-            desktop.move_(gbox, new_x as f64, new_y as f64);
+        for (x, y) in self.positions.borrow_mut().values_mut() {
+            *x -= ((*x as i32) % 5) as f64;
+            *y -= ((*y as i32) % 5) as f64;
         }
-        self.scan_positions_and_save_settings(&desktop, "", 0f64, 0f64);
+        self.apply_view(&desktop);
+        self.scan_positions_and_save_settings("", 0f64, 0f64);
     }
 
     pub(crate) fn find_cell(&mut self, mut text: String) -> i32 {
@@ -73,13 +200,23 @@ impl MetaFolder {
             self.clear_found_cells()
         }
         text = text.to_lowercase();
+        // #tag finds the cells with a tag starting with it
+        let tag = text.strip_prefix('#').map(|t| t.to_string());
         let mut matches = 0;
-        for (key, cell) in &self.cell_map {
-            let label_widget = cell.last_child().unwrap();
-            let label = label_widget.downcast::<gtk::Label>().unwrap();
-            let label_text = label.label().as_str().to_string();
-            if label_text.to_lowercase().contains(text.as_str()) {
-                cell.set_css_classes(&["icon_found"]);
+        // cells without a widget are highlighted once realized
+        for key in self.dir_items.keys() {
+            let found = match &tag {
+                Some(tag) => self.tags_of(key).iter().any(|t| t.to_lowercase().starts_with(tag.as_str())),
+                None => {
+                    let label = self.icon_styles.get(key).and_then(|s| s.label.as_ref()).map(|l| l.to_lowercase()).unwrap_or_default();
+                    let display_name = self.dir_items[key].display_name().to_lowercase();
+                    key.to_lowercase().contains(text.as_str()) || label.contains(text.as_str()) || display_name.contains(text.as_str())
+                }
+            };
+            if found {
+                if let Some(cell) = self.cell_map.get(key) {
+                    cell.add_css_class("icon_found");
+                }
                 matches += 1;
                 self.found_cells.insert(key.clone());
             }
@@ -89,18 +226,23 @@ impl MetaFolder {
 
     pub(crate) fn clear_found_cells(&mut self) {
         for key in &self.found_cells {
-            self.cell_map.get(key).unwrap().remove_css_class("icon_found");
+            if let Some(cell) = self.cell_map.get(key) {
+                cell.remove_css_class("icon_found");
+            }
         }
         self.found_cells = HashSet::new();
     }
 
-    pub(crate) fn change_cell_size(&self, cell_size: i32, save: bool) -> Option<Error> {
+    pub(crate) fn change_cell_size(&self, cell_size: i32, save: bool, w: &impl IsA<gtk::Widget>) -> Option<Error> {
+        self.cell_size.set(Some(cell_size));
+        self.sizes.borrow_mut().clear();
         for (_, cell) in &self.cell_map {
             cell.set_width_request(cell_size);
             let image_widget = cell.first_child().unwrap();
             let image = image_widget.downcast::<gtk::Image>().unwrap();
             image.set_pixel_size(cell_size);
         }
+        self.reindex(&get_desktop(w));
         if !save {
             return None;
         }
@@ -109,7 +251,32 @@ impl MetaFolder {
         files::save_settings(self.current_path.clone(), memo_folder)
     }
 
-    pub(crate) fn change_font_size(&self, style_size: String, save: bool) -> Option<Error> {
+    // gives a cell made after the folder settings were applied the cell size and font of the folder, then its own looks
+    fn style_cell(&self, name: &str, cell: &gtk::Box) {
+        if let Some(cell_size) = self.cell_size.get() {
+            cell.set_width_request(cell_size);
+            let image = cell.first_child().unwrap().downcast::<gtk::Image>().unwrap();
+            image.set_pixel_size(cell_size);
+        }
+        let icon_style = self.icon_styles.get(name).cloned().unwrap_or_default();
+        let label = cell.last_child().unwrap().downcast::<gtk::Label>().unwrap();
+        label.set_label(self.styled_label(label.label().as_str(), &icon_style).as_str());
+        if let Some(background) = icon_style.background {
+            cell.add_css_class(gtk_wrappers::tile_class(background.as_str()).as_str());
+        }
+    }
+
+    // the label markup in the looks of the folder, with those the icon overrides
+    fn styled_label(&self, label_text: &str, icon_style: &IconStyle) -> String {
+        let style = self.label_style.borrow();
+        let mut label_text = self.font_size_replacer.replace(label_text, "font_size=\"".to_owned() + &style.size + "\"").to_string();
+        let color = icon_style.color.clone().unwrap_or(style.color.clone());
+        label_text = self.font_color_replacer.replace(label_text.as_str(), "color=\"".to_owned() + &color + "\"").to_string();
+        let weight = if icon_style.bold.unwrap_or(style.bold) { "bold" } else { "normal" };
+        self.font_weight_replacer.replace(label_text.as_str(), "font_weight=\"".to_owned() + weight + "\"").to_string()
+    }
+
+    pub(crate) fn change_font_size(&self, style_size: String, save: bool, w: &impl IsA<gtk::Widget>) -> Option<Error> {
         for (_, cell) in &self.cell_map {
             let label_widget = cell.last_child().unwrap();
             let label = label_widget.downcast::<gtk::Label>().unwrap();
@@ -117,6 +284,10 @@ impl MetaFolder {
             let label_text = self.font_size_replacer.replace(label_text.as_str(), "font_size=\"".to_owned() + &style_size + "\"").to_string();
             label.set_label(label_text.as_str());
         }
+        self.label_style.borrow_mut().size = style_size.clone();
+        self.sizes.borrow_mut().clear();
+        self.style_annotations();
+        self.reindex(&get_desktop(w));
         if !save {
             return None;
         }
@@ -124,8 +295,12 @@ impl MetaFolder {
         memo_folder.font_size = style_size.to_string();
         files::save_settings(self.current_path.clone(), memo_folder)
     }
-    pub(crate) fn change_bold(&self, bold: bool, save: bool) -> Option<Error> {
-        for (_, cell) in &self.cell_map {
+    pub(crate) fn change_bold(&self, bold: bool, save: bool, w: &impl IsA<gtk::Widget>) -> Option<Error> {
+        for (name, cell) in &self.cell_map {
+            // cells with a weight of their own keep it
+            if self.icon_styles.get(name).is_some_and(|s| s.bold.is_some()) {
+                continue;
+            }
             let label_widget = cell.last_child().unwrap();
             let label = label_widget.downcast::<gtk::Label>().unwrap();
             let label_text = label.label().as_str().to_string();
@@ -133,6 +308,10 @@ impl MetaFolder {
             let label_text = self.font_weight_replacer.replace(label_text.as_str(), "font_weight=\"".to_owned() + weight + "\"").to_string();
             label.set_label(label_text.as_str());
         }
+        self.label_style.borrow_mut().bold = bold;
+        self.sizes.borrow_mut().clear();
+        self.style_annotations();
+        self.reindex(&get_desktop(w));
         if !save {
             return None;
         }
@@ -147,13 +326,19 @@ impl MetaFolder {
         let g = rgb.get(1).unwrap().parse::<u16>().unwrap();
         let b = rgb.get(2).unwrap().parse::<u16>().unwrap();
         let hex = format!("#{:X}{:X}{:X}", r, g, b);
-        for (_, cell) in &self.cell_map {
+        for (name, cell) in &self.cell_map {
+            // cells with a colour of their own keep it
+            if self.icon_styles.get(name).is_some_and(|s| s.color.is_some()) {
+                continue;
+            }
             let label_widget = cell.last_child().unwrap();
             let label = label_widget.downcast::<gtk::Label>().unwrap();
             let mut label_text = label.label().to_string();
             label_text = self.font_color_replacer.replace(label_text.as_str(), "color=\"".to_owned() + hex.as_str() + "\"").to_string();
             label.set_label(label_text.as_str());
         }
+        self.label_style.borrow_mut().color = hex.clone();
+        self.style_annotations();
         if !save {
             return None;
         }
@@ -163,32 +348,83 @@ impl MetaFolder {
     }
 
     pub(crate) fn rename_cell(&mut self, old_name: &str, new_name: &str) -> Option<Error> {
-        let cell = self.cell_map.remove(old_name).unwrap();
-        cell.set_tooltip_text(Some(new_name));
-        let image_widget = cell.first_child().unwrap();
-        image_widget.set_tooltip_text(Some(new_name));
-
-        let label_widget = cell.last_child().unwrap();
-        label_widget.set_tooltip_text(Some(new_name));
-        let label = label_widget.downcast::<gtk::Label>().unwrap();
-        let mut label_text = label.label().as_str().to_string();
-        label_text = label_text.replace(old_name, new_name);
-        label.set_label(label_text.as_str());
-        self.cell_map.insert(new_name.to_string(), cell);
+        // a cell out of view has no widget to rename
+        if let Some(cell) = self.cell_map.remove(old_name) {
+            // the tooltip follows the widget name
+            cell.set_widget_name(new_name);
+            let label_widget = cell.last_child().unwrap();
+            // an alternative label does not follow the file name
+            if self.icon_styles.get(old_name).is_none_or(|s| s.label.is_none()) {
+                let label = label_widget.downcast::<gtk::Label>().unwrap();
+                let mut label_text = label.label().as_str().to_string();
+                label_text = label_text.replace(old_name, new_name);
+                label.set_label(label_text.as_str());
+            }
+            self.cell_map.insert(new_name.to_string(), cell);
+        }
+        let size = self.sizes.borrow_mut().remove(old_name);
+        if let Some(size) = size {
+            self.sizes.borrow_mut().insert(new_name.to_string(), size);
+        }
+        self.spatial_index.borrow_mut().rename(old_name, new_name);
+        let position = self.positions.borrow_mut().remove(old_name);
+        if let Some(position) = position {
+            self.positions.borrow_mut().insert(new_name.to_string(), position);
+        }
+        let anchor = self.anchors.borrow_mut().remove(old_name);
+        if let Some(anchor) = anchor {
+            self.anchors.borrow_mut().insert(new_name.to_string(), anchor);
+        }
+        self.tooltip_details.borrow_mut().remove(old_name);
+        if let Some(custom_icon) = self.custom_icons.remove(old_name) {
+            self.custom_icons.insert(new_name.to_string(), custom_icon);
+        }
+        if let Some(icon_style) = self.icon_styles.remove(old_name) {
+            self.icon_styles.insert(new_name.to_string(), icon_style);
+        }
+        if let Some(tags) = self.tags.remove(old_name) {
+            self.tags.insert(new_name.to_string(), tags);
+        }
+        if let Some(mut dir_item) = self.dir_items.remove(old_name) {
+            dir_item.name = new_name.to_string();
+            self.dir_items.insert(new_name.to_string(), dir_item);
+        }
 
-        let mut memo_folder = load_settings(self.current_path.clone());
-        let memo_icon = memo_folder.icons.remove(old_name);
+        let mut connectors_changed = false;
+        for connector in self.connectors.iter_mut() {
+            for end in [&mut connector.from, &mut connector.to] {
+                if end == old_name {
+                    *end = new_name.to_string();
+                    connectors_changed = true;
+                }
+            }
+        }
 
-        if memo_icon.is_none() {
-            println!("Unexpected: cell {} not found", old_name)
+        // new cells have no saved position yet, they stay new under the new name
+        let is_new = self.added_cells.remove(old_name);
+        if is_new {
+            self.added_cells.insert(new_name.to_string());
+            if !connectors_changed {
+                return None;
+            }
+        }
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.connectors = self.connectors.clone();
+        if !is_new {
+            match memo_folder.icons.remove(old_name) {
+                Some(memo_icon) => { memo_folder.icons.insert(new_name.to_string(), memo_icon); }
+                None => println!("Unexpected: cell {} not found", old_name),
+            }
         }
-        memo_folder.icons.insert(new_name.to_string(), memo_icon.unwrap());
         files::save_settings(self.current_path.clone(), memo_folder)
     }
 
-    pub(crate) fn add_cell(&mut self, name: String, cell: gtk::Box) {
-        self.cell_map.insert(name.clone(), cell);
-        self.added_cells.insert(name);
+    // a cell new to the folder, realized right away so that it can be placed
+    pub(crate) fn add_cell(&mut self, desktop: &Fixed, dir_item: DirItem) {
+        let name = dir_item.name.clone();
+        self.dir_items.insert(name.clone(), dir_item);
+        self.added_cells.insert(name.clone());
+        self.realize_cell(desktop, name.as_str());
     }
 
     pub(crate) fn is_cell_newly_added(&self, name: String) -> bool {
@@ -198,16 +434,47 @@ impl MetaFolder {
     pub(crate) fn clear_added_flag(&mut self, name: String) {
         self.added_cells.remove(name.as_str());
     }
-    pub(crate) fn delete_cell(&mut self, name: String) -> (gtk::Box, Option<Error>) {
+
+    // saves the positions of all new cells as if the user had placed them
+    pub(crate) fn accept_added_cells(&mut self) -> Option<Error> {
+        self.clear_added_highlight();
+        self.added_cells = HashSet::new();
+        self.scan_positions_and_save_settings("", 0f64, 0f64)
+    }
+
+    pub(crate) fn clear_added_highlight(&self) {
+        for name in &self.added_cells {
+            if let Some(cell) = self.cell_map.get(name) {
+                cell.remove_css_class("icon_added");
+            }
+        }
+    }
+
+    // returns the widget of the cell, if realized, for the caller to take off the desktop
+    pub(crate) fn delete_cell(&mut self, name: String) -> (Option<gtk::Box>, Option<Error>) {
         let cell = self.cell_map.remove(name.as_str());
+        self.sizes.borrow_mut().remove(name.as_str());
+        self.spatial_index.borrow_mut().remove(name.as_str());
+        self.positions.borrow_mut().remove(name.as_str());
+        self.anchors.borrow_mut().remove(name.as_str());
+        self.custom_icons.remove(name.as_str());
+        self.icon_styles.remove(name.as_str());
+        self.tags.remove(name.as_str());
+        self.tooltip_details.borrow_mut().remove(name.as_str());
+        self.dir_items.remove(name.as_str());
+        self.connectors.retain(|c| c.from != name && c.to != name);
+        self.refresh_connectors();
         let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.connectors = self.connectors.clone();
         if memo_folder.icons.remove(name.as_str()).is_none() {
             println!("Unexpected: cell {} not found", name)
         }
-        (cell.unwrap(), files::save_settings(self.current_path.clone(), memo_folder))
+        (cell, files::save_settings(self.current_path.clone(), memo_folder))
     }
     pub(crate) fn zoom_and_set_zoom_widgets(&mut self, zoom_x: i32, zoom_y: i32, w: &impl IsA<gtk::Widget>) {
-        self.move_to_zoomed(zoom_x, zoom_y, w);
+        self.zoom_x = zoom_x;
+        self.zoom_y = zoom_y;
+        self.apply_view(&get_desktop(w));
         set_zoom_widgets(w, true, zoom_x, zoom_y);
     }
 
@@ -227,67 +494,289 @@ impl MetaFolder {
         files::save_settings(self.current_path.clone(), memo_folder)
     }
 
-    pub(crate) fn move_to_zoomed(&self, zoomx: i32, zoomy: i32, w: &impl IsA<gtk::Widget>) {
-        let desktop = get_desktop(w);
+    // moves every cell to its unzoomed position scaled by the current zoom - the unzoomed positions are never touched
+    pub(crate) fn apply_view(&self, desktop: &Fixed) {
+        self.resolve_anchors(desktop);
+        self.show_zones(desktop);
+        self.show_annotations(desktop);
+        for (name, gbox) in &self.cell_map {
+            if let Some((x, y)) = self.positions.borrow().get(name) {
+                self.show_at(desktop, gbox, *x, *y);
+            }
+        }
+        self.reindex(desktop);
+    }
+
+    // moves the unzoomed positions of anchored cells so that on screen they keep their distance from the window edges
+    fn resolve_anchors(&self, desktop: &Fixed) {
+        let visible = get_visible_area(desktop);
+        if visible.w <= 0f64 || visible.h <= 0f64 {
+            // not allocated yet, the stored positions are used until it is
+            return;
+        }
+        let mut positions = self.positions.borrow_mut();
+        for (name, (anchor, offset_x, offset_y)) in self.anchors.borrow().iter() {
+            if !self.dir_items.contains_key(name) {
+                continue;
+            }
+            let Some(position) = positions.get_mut(name) else { continue };
+            let (natural_width, natural_height) = self.natural_size(name);
+            let (view_x, view_y) = self.model_to_view(position.0, position.1);
+            let b = Bounds::new(view_x, view_y, natural_width * self.view_scale, natural_height * self.view_scale);
+            let (view_x, view_y) = Self::anchored_position(*anchor, (*offset_x, *offset_y), &b, &visible);
+            *position = self.view_to_model(view_x.max(0f64), view_y.max(0f64));
+        }
+    }
+
+    // where the cell with bounds b goes to keep its offsets from the anchored edges; the other axis stays as it is
+    fn anchored_position(anchor: Anchor, (offset_x, offset_y): (i32, i32), b: &Bounds, visible: &Bounds) -> (f64, f64) {
+        let x = if anchor.is_left() {
+            visible.x + offset_x as f64
+        } else if anchor.is_right() {
+            visible.x + visible.w - offset_x as f64 - b.w
+        } else {
+            b.x
+        };
+        let y = if anchor.is_top() {
+            visible.y + offset_y as f64
+        } else if anchor.is_bottom() {
+            visible.y + visible.h - offset_y as f64 - b.h
+        } else {
+            b.y
+        };
+        (x, y)
+    }
+
+    // keeps the anchored cells at their window edges while the desktop scrolls
+    pub(crate) fn follow_anchors(&self, desktop: &Fixed) {
+        if self.anchors.borrow().is_empty() {
+            return;
+        }
+        self.resolve_anchors(desktop);
+        for name in self.anchors.borrow().keys() {
+            let Some((x, y)) = self.positions.borrow().get(name).copied() else { continue };
+            if let Some(cell) = self.cell_map.get(name) {
+                self.show_at(desktop, cell, x, y);
+            }
+            // the widget is only moved at the next layout, so the bounds come from the position
+            let (view_x, view_y) = self.model_to_view(x, y);
+            let (width, height) = self.natural_size(name);
+            self.spatial_index.borrow_mut().insert(name, Bounds::new(view_x, view_y, width * self.view_scale, height * self.view_scale));
+        }
+        self.refresh_connectors();
+    }
+
+    // distances of the cell bounds from the edges of the visible area the anchor refers to, all in view coordinates
+    fn anchor_offsets(anchor: Anchor, b: &Bounds, visible: &Bounds) -> (i32, i32) {
+        let offset_x = if anchor.is_right() { visible.x + visible.w - b.x - b.w } else { b.x - visible.x };
+        let offset_y = if anchor.is_bottom() { visible.y + visible.h - b.y - b.h } else { b.y - visible.y };
+        (offset_x.round() as i32, offset_y.round() as i32)
+    }
+
+    pub(crate) fn set_anchor(&mut self, desktop: &Fixed, names: &[String], anchor: Anchor) -> Option<Error> {
+        if self.locked {
+            return None;
+        }
+        let visible = get_visible_area(desktop);
+        for name in names {
+            if anchor == Anchor::None {
+                self.anchors.borrow_mut().remove(name);
+                continue;
+            }
+            let Some(bounds) = self.spatial_index.borrow().get(name) else { continue };
+            let (offset_x, offset_y) = Self::anchor_offsets(anchor, &bounds, &visible);
+            self.anchors.borrow_mut().insert(name.clone(), (anchor, offset_x, offset_y));
+            // anchoring is as deliberate as placing the cell by hand
+            if self.added_cells.remove(name) {
+                if let Some(cell) = self.cell_map.get(name) {
+                    cell.remove_css_class("icon_added");
+                }
+            }
+        }
+        self.scan_positions_and_save_settings("", 0f64, 0f64)
+    }
+
+    // the cells an action on the given cell applies to: all found cells if it is one of them, else just the cell
+    pub(crate) fn selection_for(&self, name: &str) -> Vec<String> {
+        if self.found_cells.contains(name) {
+            let mut names: Vec<String> = self.found_cells.iter().cloned().collect();
+            names.sort();
+            return names;
+        }
+        vec![name.to_string()]
+    }
+
+    pub(crate) fn model_to_view(&self, x: f64, y: f64) -> (f64, f64) {
+        let (origin_x, origin_y) = self.origin.get();
+        (zoom::zoomed(x, self.zoom_x) * self.view_scale + origin_x, zoom::zoomed(y, self.zoom_y) * self.view_scale + origin_y)
+    }
+
+    pub(crate) fn view_to_model(&self, x: f64, y: f64) -> (f64, f64) {
+        let (origin_x, origin_y) = self.origin.get();
+        (zoom::unzoomed((x - origin_x) / self.view_scale, self.zoom_x), zoom::unzoomed((y - origin_y) / self.view_scale, self.zoom_y))
+    }
+
+    // grows the canvas by the given amounts of view pixels; growing left or up moves the whole layout right or down
+    pub(crate) fn extend_canvas(&self, desktop: &Fixed, left: f64, top: f64, right: f64, bottom: f64) {
+        let (origin_x, origin_y) = self.origin.get();
+        self.origin.set((origin_x + left, origin_y + top));
+        let (extra_width, extra_height) = self.extra_size.get();
+        self.extra_size.set((extra_width + right, extra_height + bottom));
+        if left != 0f64 || top != 0f64 {
+            self.apply_view(desktop);
+        } else {
+            self.update_extent(desktop);
+        }
+    }
+
+    // the desktop is at least as large as the layout plus the room added to it
+    pub(crate) fn update_extent(&self, desktop: &Fixed) {
+        let (right, bottom) = self.spatial_index.borrow().extent();
+        let (extra_width, extra_height) = self.extra_size.get();
+        desktop.set_size_request((right + extra_width) as i32, (bottom + extra_height) as i32);
+        minimap::refresh_minimap(desktop);
+        self.request_virtualize(desktop);
+        if let Some(layer) = &self.connector_layer {
+            layer.set_size_request((right + extra_width) as i32, (bottom + extra_height) as i32);
+        }
+        self.refresh_connectors();
+    }
+
+    // shifts the unzoomed positions of the cells, zones, annotations and inbox so that none is negative, moving the origin the other way so nothing moves on screen
+    // the shift is whole pixels so that zones and annotations stay where they were relative to the cells; false when nothing moved
+    fn normalize_positions(&mut self) -> bool {
+        let mut positions = self.positions.borrow_mut();
+        let corners: Vec<(f64, f64)> = positions.values().copied()
+            .chain(self.zones.iter().map(|z| (z.x as f64, z.y as f64)))
+            // lines and arrows may end left of or above their start
+            .chain(self.annotations.iter().map(|a| (a.x.min(a.x + a.width) as f64, a.y.min(a.y + a.height) as f64)))
+            .chain(self.inbox.iter().map(|i| (i.x as f64, i.y as f64)))
+            .collect();
+        let min_x = corners.iter().map(|(x, _)| *x).fold(0f64, f64::min).floor();
+        let min_y = corners.iter().map(|(_, y)| *y).fold(0f64, f64::min).floor();
+        if min_x == 0f64 && min_y == 0f64 {
+            return false;
+        }
+        for (x, y) in positions.values_mut() {
+            *x -= min_x;
+            *y -= min_y;
+        }
+        for zone in self.zones.iter_mut() {
+            zone.x -= min_x as i32;
+            zone.y -= min_y as i32;
+        }
+        for annotation in self.annotations.iter_mut() {
+            annotation.x -= min_x as i32;
+            annotation.y -= min_y as i32;
+        }
+        if let Some(inbox) = self.inbox.as_mut() {
+            inbox.x -= min_x as i32;
+            inbox.y -= min_y as i32;
+        }
+        let (origin_x, origin_y) = self.origin.get();
+        self.origin.set((origin_x + zoom::zoomed(min_x, self.zoom_x) * self.view_scale, origin_y + zoom::zoomed(min_y, self.zoom_y) * self.view_scale));
+        true
+    }
 
-        for (_, gbox) in &self.cell_map {
-            let current_pos = get_widget_bounds(&desktop, gbox);
-            let new_x = current_pos.x() * (zoomx as f32 / 100f32);
-            let new_y = current_pos.y() * (zoomy as f32 / 100f32);
-            desktop.move_(gbox, new_x as f64, new_y as f64)
+    // x and y are unzoomed positions
+    fn show_at(&self, desktop: &Fixed, cell: &impl IsA<gtk::Widget>, x: f64, y: f64) {
+        let (view_x, view_y) = self.model_to_view(x, y);
+        if self.view_scale == 1f64 {
+            desktop.move_(cell, view_x, view_y);
+            return;
         }
+        let transform = Transform::new()
+            .translate(&Point::new(view_x as f32, view_y as f32))
+            .scale(self.view_scale as f32, self.view_scale as f32);
+        desktop.set_child_transform(cell, Some(&transform));
+    }
+
+    pub(crate) fn set_view_scale(&mut self, scale: f64, desktop: &Fixed) {
+        self.view_scale = scale;
+        self.apply_view(desktop);
     }
+
     pub(crate) fn zoom(&mut self, zoomx: i32, zoomy: i32, w: &impl IsA<gtk::Widget>) -> bool {
         if self.zoom_x == zoomx && self.zoom_y == zoomy {
-            println!("equal");
             //don't save settings on a false movement
             return false;
         }
         self.zoom = true;
+        self.zoom_x = zoomx;
+        self.zoom_y = zoomy;
+        self.apply_view(&get_desktop(w));
+        true
+    }
 
-        if self.zoom_x == 0 {
-            self.zoom_x = 100;
-            self.zoom_y = 100;
+    // zooms so that all cells fit in the window, without saving
+    pub(crate) fn fit_to_window(&mut self, w: &impl IsA<gtk::Widget>) -> bool {
+        let scrolled_window = get_scrolled_window(w);
+        let width = scrolled_window.width() as f64 / self.view_scale;
+        let height = scrolled_window.height() as f64 / self.view_scale;
+        if width <= 0f64 || height <= 0f64 {
+            return false;
         }
-        let zxf = (zoomx as f32) / 100f32;
-        let zyf = (zoomy as f32) / 100f32;
-        let desktop = get_desktop(w);
+        let cells: Vec<(f64, f64, f64, f64)> = self.positions.borrow().iter()
+            .filter(|(name, _)| self.dir_items.contains_key(*name))
+            .map(|(name, (x, y))| {
+                let (natural_width, natural_height) = self.natural_size(name);
+                (*x, *y, natural_width, natural_height)
+            })
+            .collect();
+        let (zoom_x, zoom_y) = zoom::fit_zoom(&cells, width, height);
+        let changed = self.zoom(zoom_x, zoom_y, w);
+        set_zoom_widgets(w, true, zoom_x, zoom_y);
+        changed
+    }
 
-        for (_, gbox) in &self.cell_map {
-            let current_pos = get_widget_bounds(&desktop, gbox);
-            let unzoomed_x = (current_pos.x() / self.zoom_x as f32) * 100f32;
-            let unzoomed_y = (current_pos.y() / self.zoom_y as f32) * 100f32;
-            let new_x = unzoomed_x * zxf;
-            let new_y = unzoomed_y * zyf;
-            desktop.move_(gbox, new_x as f64, new_y as f64)
+    pub(crate) fn fit_and_save_settings(&mut self, w: &impl IsA<gtk::Widget>) -> Option<Error> {
+        if self.fit_to_window(w) {
+            return self.save_zoom_settings(true, self.zoom_x, self.zoom_y);
         }
-
-        self.zoom_x = zoomx;
-        self.zoom_y = zoomy;
-        true
+        None
     }
 
     pub fn zoom_commit_and_save_settings(&mut self, w: &impl IsA<gtk::Widget>) -> Option<Error> {
+        if self.locked {
+            return None;
+        }
+        self.normalize_positions();
+        // the zoomed positions become the new unzoomed ones, those of the zones, annotations and inbox too
+        for (x, y) in self.positions.borrow_mut().values_mut() {
+            *x = zoom::zoomed(*x, self.zoom_x).round();
+            *y = zoom::zoomed(*y, self.zoom_y).round();
+        }
+        for zone in self.zones.iter_mut() {
+            (zone.x, zone.y, zone.width, zone.height) = zoom::zoomed_rect(zone.x, zone.y, zone.width, zone.height, self.zoom_x, self.zoom_y);
+        }
+        if let Some(inbox) = self.inbox.as_mut() {
+            (inbox.x, inbox.y, inbox.width, inbox.height) = zoom::zoomed_rect(inbox.x, inbox.y, inbox.width, inbox.height, self.zoom_x, self.zoom_y);
+        }
+        // annotations keep their size like cells
+        for annotation in self.annotations.iter_mut() {
+            annotation.x = zoom::zoomed(annotation.x as f64, self.zoom_x).round() as i32;
+            annotation.y = zoom::zoomed(annotation.y as f64, self.zoom_y).round() as i32;
+        }
         self.zoom = false;
         self.zoom_x = 0;
         self.zoom_y = 0;
+        self.apply_view(&get_desktop(w));
 
+        let mut memo_folder = load_settings(self.current_path.clone());
         let mut icons: HashMap<String, MemoIcon> = HashMap::new();
-        let desktop = get_desktop(w);
-        for (path, gbox) in &self.cell_map {
-            let bounds = gtk_wrappers::get_widget_bounds(&desktop, &gbox);
-            let memo_icon = MemoIcon {
-                position_x: bounds.x() as i32,
-                position_y: bounds.y() as i32,
-            };
+        for (path, (x, y)) in self.positions.borrow().iter() {
+            let mut memo_icon = memo_folder.icons.remove(path).unwrap_or_default();
+            memo_icon.position_x = x.round() as i32;
+            memo_icon.position_y = y.round() as i32;
             icons.insert(path.to_string(), memo_icon);
         }
-
-        let mut memo_folder = load_settings(self.current_path.clone());
         memo_folder.zoom = self.zoom;
         memo_folder.zoom_x = self.zoom_x;
         memo_folder.zoom_y = self.zoom_y;
         memo_folder.icons = icons;
+        memo_folder.zones = self.zones.clone();
+        memo_folder.annotations = self.annotations.clone();
+        memo_folder.inbox = self.inbox;
 
         set_zoom_widgets(w, false, 100, 100);
         files::save_settings(self.current_path.clone(), memo_folder)
@@ -299,48 +788,670 @@ impl MetaFolder {
         if self.zoom_x == 0 {
             return;
         }
-        let desktop = get_desktop(w);
-
-        for (_, gbox) in &self.cell_map {
-            let current_pos = get_widget_bounds(&desktop, gbox);
-            let unzoomed_x = (current_pos.x() / self.zoom_x as f32) * 100f32;
-            let unzoomed_y = (current_pos.y() / self.zoom_y as f32) * 100f32;
-            desktop.move_(gbox, unzoomed_x as f64, unzoomed_y as f64)
-        }
         self.zoom_x = 0;
         self.zoom_y = 0;
+        self.apply_view(&get_desktop(w));
         set_zoom_widgets(w, false, 100, 100);
     }
     pub fn unzoom_and_save_settings(&mut self, w: &impl IsA<gtk::Widget>) -> Option<Error> {
         self.unzoom(w);
         self.save_zoom_settings(false, 0, 0)
     }
-    pub(crate) fn scan_positions_and_save_settings(&self, desktop: &Fixed, icon_file_path: &str, x: f64, y: f64) -> Option<Error> {
+    // x and y are desktop (view) coordinates
+    pub(crate) fn scan_positions_and_save_settings(&mut self, icon_file_path: &str, x: f64, y: f64) -> Option<Error> {
+        self.normalize_positions();
         let mut memo_folder = load_settings(self.current_path.clone());
         let mut icons: HashMap<String, MemoIcon> = HashMap::new();
-        let ds = get_application(desktop);
-        for (path, gbox) in &self.cell_map {
-            let memo_icon: MemoIcon;
+        for (path, (position_x, position_y)) in self.positions.borrow().iter() {
+            let mut memo_icon = memo_folder.icons.remove(path).unwrap_or_default();
+            let (anchor, offset_x, offset_y) = self.anchors.borrow().get(path).copied().unwrap_or_default();
+            memo_icon.anchor = anchor;
+            memo_icon.anchor_offset_x = offset_x;
+            memo_icon.anchor_offset_y = offset_y;
+            memo_icon.custom_icon = self.custom_icons.get(path).cloned();
+            memo_icon.style = self.icon_styles.get(path).cloned().unwrap_or_default();
             if path == icon_file_path {
-                memo_icon = MemoIcon {
-                    position_x: x as i32,
-                    position_y: y as i32,
-                };
+                let (model_x, model_y) = self.view_to_model(x, y);
+                memo_icon.position_x = model_x.round() as i32;
+                memo_icon.position_y = model_y.round() as i32;
             } else {
                 // don't consider newly added cells as having been deliberately placed where they are by the user
                 // and thereby don't save their position
-                if ds.imp().metafolder.borrow().added_cells.contains(path) {
+                if self.added_cells.contains(path) {
                     continue;
                 }
-                let bounds = gtk_wrappers::get_widget_bounds(desktop, &gbox);
-                memo_icon = MemoIcon {
-                    position_x: bounds.x() as i32,
-                    position_y: bounds.y() as i32,
-                };
+                memo_icon.position_x = position_x.round() as i32;
+                memo_icon.position_y = position_y.round() as i32;
             }
             icons.insert(path.to_string(), memo_icon);
         }
         memo_folder.icons = icons;
+        memo_folder.zones = self.zones.clone();
+        memo_folder.annotations = self.annotations.clone();
+        memo_folder.inbox = self.inbox;
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    // files gone since the last time lose their settings at the next save, hidden and ignored ones keep theirs
+    pub(crate) fn forget_missing_files(&self) {
+        self.positions.borrow_mut().retain(|name, _| self.dir_items.contains_key(name) || self.unshown.contains(name));
+    }
+
+    // rebuilds the spatial index from all cells, realized or not
+    pub(crate) fn reindex(&self, desktop: &Fixed) {
+        let mut index = self.spatial_index.borrow_mut();
+        index.clear();
+        for name in self.dir_items.keys() {
+            if let Some(bounds) = self.cell_bounds(desktop, name) {
+                index.insert(name, bounds);
+            }
+        }
+        drop(index);
+        self.update_extent(desktop);
+    }
+
+    // view bounds of a cell: measured when realized, else from its position and last known size; None until placed
+    fn cell_bounds(&self, desktop: &Fixed, name: &str) -> Option<Bounds> {
+        if let Some(cell) = self.cell_map.get(name) {
+            let bounds = get_cell_bounds(desktop, cell)?;
+            let (_, natural) = cell.preferred_size();
+            let size = (natural.width() as f64, natural.height() as f64);
+            self.sizes.borrow_mut().insert(name.to_string(), size);
+            self.typical_size.set(size);
+            return Some(bounds);
+        }
+        let (x, y) = *self.positions.borrow().get(name)?;
+        let (view_x, view_y) = self.model_to_view(x, y);
+        let (width, height) = self.natural_size(name);
+        Some(Bounds::new(view_x, view_y, width * self.view_scale, height * self.view_scale))
+    }
+
+    // unscaled size of a cell; cells never realized are assumed to be as large as the last one measured
+    fn natural_size(&self, name: &str) -> (f64, f64) {
+        if let Some(cell) = self.cell_map.get(name) {
+            let (_, natural) = cell.preferred_size();
+            return (natural.width() as f64, natural.height() as f64);
+        }
+        self.sizes.borrow().get(name).copied().unwrap_or(self.typical_size.get())
+    }
+
+    // gives widgets to the cells in or near the part of the desktop in view and takes them from those far from it,
+    // so that huge folders cost only the widgets on screen
+    // virtualize runs once the main loop is idle, however often it is asked for until then
+    pub(crate) fn request_virtualize(&self, desktop: &Fixed) {
+        if !self.needs_virtualize.replace(true) {
+            virtualize_when_idle(desktop);
+        }
+    }
+
+    pub(crate) fn virtualize(&mut self, desktop: &Fixed) {
+        self.needs_virtualize.set(false);
+        let visible = get_visible_area(desktop);
+        let near = Bounds::new(visible.x - REALIZE_MARGIN, visible.y - REALIZE_MARGIN, visible.w + 2f64 * REALIZE_MARGIN, visible.h + 2f64 * REALIZE_MARGIN);
+        let index = self.spatial_index.borrow();
+        let to_realize: Vec<String> = index.overlapping_all(&near, 0f64).into_iter()
+            .filter(|name| !self.cell_map.contains_key(name))
+            .collect();
+        let dragged = self.dragged.borrow().clone();
+        // new cells stay realized: they are highlighted and listed with their picture among the arrivals
+        let to_drop: Vec<String> = self.cell_map.keys()
+            .filter(|name| !self.added_cells.contains(*name) && dragged.as_ref() != Some(*name))
+            .filter(|name| !index.get(name).is_some_and(|b| b.overlaps(&near, REALIZE_MARGIN)))
+            .cloned()
+            .collect();
+        drop(index);
+        if to_realize.is_empty() && to_drop.is_empty() {
+            return;
+        }
+        for name in to_drop {
+            let cell = self.cell_map.remove(&name).unwrap();
+            desktop.remove(&cell);
+        }
+        for name in &to_realize {
+            self.realize_cell(desktop, name);
+            // the measured bounds replace the estimated ones
+            if let Some(bounds) = self.cell_bounds(desktop, name) {
+                self.spatial_index.borrow_mut().insert(name, bounds);
+            }
+        }
+        self.update_extent(desktop);
+        self.needs_virtualize.set(false);
+    }
+
+    // makes the widget of a cell, on the desktop at its position if it has one
+    fn realize_cell(&mut self, desktop: &Fixed, name: &str) {
+        let Some(dir_item) = self.dir_items.get(name) else { return };
+        let custom_icon = self.custom_icons.get(name);
+        let label = self.icon_styles.get(name).and_then(|s| s.label.as_ref());
+        let cell = cell::make_cell(dir_item, custom_icon, label, ICON_SIZE);
+        cell.add_controller(cell::make_drag_source(name.to_string(), &cell, desktop, true));
+        self.style_cell(name, &cell);
+        tags::add_emblem_row(&cell, dir_item, &self.tags_of(name));
+        // a custom icon is never replaced by a thumbnail
+        if custom_icon.is_none() {
+            cell::load_thumbnail(&cell, self.current_path.clone(), dir_item, &self.load_cancellable);
+        }
+        if self.added_cells.contains(name) {
+            cell.add_css_class("icon_added");
+        }
+        if self.found_cells.contains(name) {
+            cell.add_css_class("icon_found");
+        }
+        let position = self.positions.borrow().get(name).copied();
+        if let Some((x, y)) = position {
+            desktop.put(&cell, 0f64, 0f64);
+            self.show_at(desktop, &cell, x, y);
+        }
+        self.cell_map.insert(name.to_string(), cell);
+    }
+
+    // None goes back to the icon of the file
+    pub(crate) fn set_custom_icon(&mut self, desktop: &Fixed, name: &str, custom_icon: Option<String>) -> Option<Error> {
+        if !self.dir_items.contains_key(name) {
+            return None;
+        }
+        match custom_icon {
+            Some(custom_icon) => self.custom_icons.insert(name.to_string(), custom_icon),
+            None => self.custom_icons.remove(name),
+        };
+        // choosing an icon is as deliberate as placing the cell by hand
+        self.added_cells.remove(name);
+        self.refresh_cell(desktop, name);
+        self.scan_positions_and_save_settings("", 0f64, 0f64)
+    }
+
+    // an empty style goes back to the looks of the folder
+    pub(crate) fn set_icon_style(&mut self, desktop: &Fixed, name: &str, icon_style: IconStyle) -> Option<Error> {
+        if !self.dir_items.contains_key(name) {
+            return None;
+        }
+        if icon_style.is_empty() {
+            self.icon_styles.remove(name);
+        } else {
+            self.icon_styles.insert(name.to_string(), icon_style);
+        }
+        // as deliberate as placing the cell by hand
+        self.added_cells.remove(name);
+        self.refresh_cell(desktop, name);
+        self.scan_positions_and_save_settings("", 0f64, 0f64)
+    }
+
+    // tags of the folder settings, with those of the extended attributes of the file while these are in use
+    pub(crate) fn tags_of(&self, name: &str) -> Vec<String> {
+        let mut tags = self.tags.get(name).cloned().unwrap_or_default();
+        if self.xattr_tags {
+            if let Some(dir_item) = self.dir_items.get(name) {
+                tags.extend(dir_item.xattr_tags.iter().filter(|t| !tags.contains(t)).cloned().collect::<Vec<String>>());
+            }
+        }
+        tags
+    }
+
+    pub(crate) fn set_tag(&mut self, desktop: &Fixed, names: &[String], tag: &str, on: bool) -> Option<Error> {
+        for name in names {
+            let mut tags = self.tags_of(name);
+            tags.retain(|t| t != tag);
+            if on {
+                tags.push(tag.to_string());
+            }
+            if self.xattr_tags {
+                self.write_tags_attribute(name, &tags);
+            }
+            if tags.is_empty() {
+                self.tags.remove(name);
+            } else {
+                self.tags.insert(name.clone(), tags);
+            }
+            self.refresh_cell(desktop, name);
+        }
+        self.save_tags()
+    }
+
+    // turning the extended attributes on writes the tags of all files to them, so that other tools see them right away
+    pub(crate) fn set_xattr_tags(&mut self, desktop: &Fixed, xattr_tags: bool) -> Option<Error> {
+        self.xattr_tags = xattr_tags;
+        let names: Vec<String> = self.dir_items.iter()
+            .filter(|(name, dir_item)| self.tags.contains_key(*name) || !dir_item.xattr_tags.is_empty())
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in &names {
+            if xattr_tags {
+                self.write_tags_attribute(name, &self.tags_of(name));
+            }
+            self.refresh_cell(desktop, name);
+        }
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.xattr_tags = xattr_tags;
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    fn write_tags_attribute(&mut self, name: &str, tags: &[String]) {
+        let Some(dir_item) = self.dir_items.get_mut(name) else { return };
+        if dir_item.target.is_some() {
+            return;
+        }
+        tags::write_tags_attribute((self.current_path.clone() + name).as_str(), tags);
+        dir_item.xattr_tags = tags.to_vec();
+    }
+
+    fn save_tags(&self) -> Option<Error> {
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.tags = self.tags.clone();
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    // remakes the widget of a realized cell after its looks changed
+    fn refresh_cell(&mut self, desktop: &Fixed, name: &str) {
+        let Some(cell) = self.cell_map.remove(name) else { return };
+        desktop.remove(&cell);
+        self.realize_cell(desktop, name);
+        if let Some(bounds) = self.cell_bounds(desktop, name) {
+            self.spatial_index.borrow_mut().insert(name, bounds);
+        }
+        self.update_extent(desktop);
+    }
+
+    pub(crate) fn move_cell(&self, desktop: &Fixed, name: &str, x: f64, y: f64) {
+        let (model_x, model_y) = self.view_to_model(x, y);
+        self.positions.borrow_mut().insert(name.to_string(), (model_x, model_y));
+        if let Some(cell) = self.cell_map.get(name) {
+            self.show_at(desktop, cell, model_x, model_y);
+        }
+        if let Some(bounds) = self.cell_bounds(desktop, name) {
+            self.spatial_index.borrow_mut().insert(name, bounds);
+            // a moved anchored cell keeps its new distance from the edges
+            let mut anchors = self.anchors.borrow_mut();
+            if let Some((anchor, offset_x, offset_y)) = anchors.get_mut(name) {
+                (*offset_x, *offset_y) = Self::anchor_offsets(*anchor, &bounds, &get_visible_area(desktop));
+            }
+        }
+        self.update_extent(desktop);
+    }
+
+    pub(crate) fn cells_underneath(&self, name: &str, bounds: &Bounds) -> Vec<String> {
+        let mut found = self.spatial_index.borrow().overlapping_all(bounds, self.spacing as f64);
+        found.retain(|n| n != name);
+        found
+    }
+
+    pub(crate) fn free_spot_near(&self, name: &str) -> (f64, f64) {
+        let index = self.spatial_index.borrow();
+        match index.get(name) {
+            None => (0f64, 0f64),
+            Some(bounds) => index.find_free_spot_near(name, &bounds, self.spacing as f64),
+        }
+    }
+
+    // puts a cell that is not on the desktop yet where the placement strategy of the folder says; never moves other cells
+    pub(crate) fn place_new_cell(&self, desktop: &Fixed, name: &str) {
+        let cell = self.get_cell(name.to_string());
+        let (_, natural) = cell.preferred_size();
+        let w = natural.width() as f64 * self.view_scale;
+        let h = natural.height() as f64 * self.view_scale;
+        let spacing = self.spacing as f64;
+        let visible_width = get_visible_width(desktop) as f64;
+
+        let index = self.spatial_index.borrow();
+        let (x, y) = match self.placement {
+            PlacementStrategy::FirstFree => index.find_free_spot(0f64, 0f64, w, h, spacing, INITIAL_DESKTOP_WIDTH as f64),
+            PlacementStrategy::VisibleWidth => index.find_free_spot(0f64, 0f64, w, h, spacing, visible_width),
+            PlacementStrategy::Inbox => {
+                match self.inbox {
+                    // a full inbox leaves new files to the default placement
+                    Some(inbox) => index.find_free_spot_in(&self.inbox_view_bounds(&inbox), w, h, spacing)
+                        .unwrap_or_else(|| index.find_free_spot(0f64, 0f64, w, h, spacing, visible_width)),
+                    None => index.find_free_spot(0f64, 0f64, w, h, spacing, visible_width),
+                }
+            }
+            PlacementStrategy::SameType => {
+                match self.same_type_anchor(name) {
+                    Some(anchor) => index.find_free_spot_near(name, &Bounds::new(anchor.x, anchor.y, w, h), spacing),
+                    None => index.find_free_spot(0f64, 0f64, w, h, spacing, visible_width),
+                }
+            }
+            PlacementStrategy::EndByName | PlacementStrategy::EndByDate => {
+                index.find_free_spot(0f64, self.layout_bottom() + spacing, w, h, spacing, visible_width)
+            }
+            PlacementStrategy::Zone => {
+                let zone = self.dir_items.get(name).and_then(|dir_item| self.zones.iter().find(|zone| zone.accepts(dir_item)));
+                match zone {
+                    Some(zone) => {
+                        let b = self.zone_view_bounds(zone);
+                        // below the title
+                        index.find_free_spot(b.x + spacing, b.y + zones::ZONE_TITLE_HEIGHT, w, h, spacing, b.x + b.w)
+                    }
+                    None => index.find_free_spot(0f64, 0f64, w, h, spacing, visible_width),
+                }
+            }
+        };
+        drop(index);
+
+        desktop.put(cell, x, y);
+        let (model_x, model_y) = self.view_to_model(x, y);
+        self.positions.borrow_mut().insert(name.to_string(), (model_x, model_y));
+        self.show_at(desktop, cell, model_x, model_y);
+        self.spatial_index.borrow_mut().insert(name, Bounds::new(x, y, w, h));
+        self.update_extent(desktop);
+    }
+
+    // the placed cell of the same mime type closest to the center of all of them
+    fn same_type_anchor(&self, name: &str) -> Option<Bounds> {
+        let mime_type = &self.dir_items.get(name)?.mime_type;
+        let index = self.spatial_index.borrow();
+        let same_type: Vec<Bounds> = self.dir_items.values()
+            .filter(|dir_item| dir_item.name != name && &dir_item.mime_type == mime_type)
+            .filter_map(|dir_item| index.get(dir_item.name.as_str()))
+            .collect();
+        if same_type.is_empty() {
+            return None;
+        }
+        let center_x = same_type.iter().map(|b| b.x).sum::<f64>() / same_type.len() as f64;
+        let center_y = same_type.iter().map(|b| b.y).sum::<f64>() / same_type.len() as f64;
+        same_type.into_iter().min_by(|a, b| {
+            let da = (a.x - center_x).powi(2) + (a.y - center_y).powi(2);
+            let db = (b.x - center_x).powi(2) + (b.y - center_y).powi(2);
+            da.total_cmp(&db)
+        })
+    }
+
+    // lowest edge of the cells placed by the user
+    fn layout_bottom(&self) -> f64 {
+        let index = self.spatial_index.borrow();
+        self.dir_items.keys()
+            .filter(|name| !self.added_cells.contains(*name))
+            .filter_map(|name| index.get(name))
+            .map(|b| b.y + b.h)
+            .fold(0f64, f64::max)
+    }
+
+    // order in which new cells are placed
+    pub(crate) fn sorted_added_cells(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.added_cells.iter().cloned().collect();
+        if self.placement == PlacementStrategy::EndByDate {
+            names.sort_by_key(|name| (self.dir_items.get(name).map(|dir_item| dir_item.modified).unwrap_or(0), name.clone()));
+        } else {
+            names.sort();
+        }
+        names
+    }
+
+    pub(crate) fn set_placement(&mut self, placement: PlacementStrategy) -> Option<Error> {
+        if self.placement == placement {
+            return None;
+        }
+        self.placement = placement;
+
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.placement = placement;
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    // whether a file of the folder gets a cell: the same rules for loading, the folder monitor and thus search
+    pub(crate) fn is_shown(&self, dir_item: &DirItem) -> bool {
+        files::is_shown(dir_item, self.show_hidden, &self.ignore_rules)
+    }
+
+    pub(crate) fn set_show_hidden(&mut self, show_hidden: bool) -> Option<Error> {
+        self.show_hidden = show_hidden;
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.show_hidden = show_hidden;
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    pub(crate) fn set_locked(&mut self, locked: bool) -> Option<Error> {
+        if self.locked == locked {
+            return None;
+        }
+        self.locked = locked;
+
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.locked = locked;
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    pub(crate) fn set_inbox(&mut self, inbox: Option<MemoRect>) -> Option<Error> {
+        self.inbox = inbox;
+
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.inbox = inbox;
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    pub(crate) fn set_spacing(&mut self, spacing: i32) -> Option<Error> {
+        self.spacing = spacing;
+
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.spacing = spacing;
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    pub(crate) fn zone_view_bounds(&self, zone: &MemoZone) -> Bounds {
+        self.rect_view_bounds(zone.x, zone.y, zone.width, zone.height)
+    }
+
+    // kept unzoomed so that it stays on the same part of the folder whatever the view
+    pub(crate) fn inbox_from_view(&self, visible: &Bounds) -> MemoRect {
+        let (x, y) = self.view_to_model(visible.x, visible.y);
+        let (right, bottom) = self.view_to_model(visible.x + visible.w, visible.y + visible.h);
+        let (x, y) = (x.round() as i32, y.round() as i32);
+        MemoRect { x, y, width: right.round() as i32 - x, height: bottom.round() as i32 - y }
+    }
+
+    fn inbox_view_bounds(&self, inbox: &MemoRect) -> Bounds {
+        self.rect_view_bounds(inbox.x, inbox.y, inbox.width, inbox.height)
+    }
+
+    // view bounds of an unzoomed area: its size follows the zoom
+    fn rect_view_bounds(&self, x: i32, y: i32, width: i32, height: i32) -> Bounds {
+        let (view_x, view_y) = self.model_to_view(x as f64, y as f64);
+        let (right, bottom) = self.model_to_view((x + width) as f64, (y + height) as f64);
+        Bounds::new(view_x, view_y, right - view_x, bottom - view_y)
+    }
+
+    // zones are not transformed like cells: their size follows the zoom, their title does not
+    pub(crate) fn show_zones(&self, desktop: &Fixed) {
+        for (zone, widget) in self.zones.iter().zip(&self.zone_widgets) {
+            let b = self.zone_view_bounds(zone);
+            desktop.move_(widget, b.x, b.y);
+            widget.set_size_request(b.w as i32, b.h as i32);
+        }
+    }
+
+    // zones are kept below all cells
+    pub(crate) fn add_zone(&mut self, desktop: &Fixed, zone: MemoZone, widget: gtk::Overlay) {
+        desktop.put(&widget, 0f64, 0f64);
+        widget.insert_after(desktop, None::<&gtk::Widget>);
+        self.zones.push(zone);
+        self.zone_widgets.push(widget);
+        self.show_zones(desktop);
+    }
+
+    pub(crate) fn delete_zone(&mut self, desktop: &Fixed, index: usize) -> Option<Error> {
+        self.zones.remove(index);
+        desktop.remove(&self.zone_widgets.remove(index));
+        self.save_zones()
+    }
+
+    pub(crate) fn zone_index(&self, widget: &gtk::Overlay) -> Option<usize> {
+        self.zone_widgets.iter().position(|w| w == widget)
+    }
+
+    // cells whose center lies in the zone
+    pub(crate) fn cells_in_zone(&self, index: usize) -> Vec<String> {
+        let zone_bounds = self.zone_view_bounds(&self.zones[index]);
+        let spatial_index = self.spatial_index.borrow();
+        self.dir_items.keys()
+            .filter(|name| spatial_index.get(name).is_some_and(|b| {
+                let (center_x, center_y) = (b.x + b.w / 2f64, b.y + b.h / 2f64);
+                center_x >= zone_bounds.x && center_x < zone_bounds.x + zone_bounds.w &&
+                    center_y >= zone_bounds.y && center_y < zone_bounds.y + zone_bounds.h
+            }))
+            .cloned()
+            .collect()
+    }
+
+    // a zone moved to a negative position shifts the whole layout back
+    pub(crate) fn save_zones(&mut self) -> Option<Error> {
+        if self.normalize_positions() {
+            return self.scan_positions_and_save_settings("", 0f64, 0f64);
+        }
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.zones = self.zones.clone();
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    // annotations are scaled with the view like cells
+    pub(crate) fn show_annotations(&self, desktop: &Fixed) {
+        for (annotation, widget) in self.annotations.iter().zip(&self.annotation_widgets) {
+            let (x, y) = annotations::widget_origin(annotation);
+            self.show_at(desktop, widget, x, y);
+        }
+    }
+
+    pub(crate) fn style_annotations(&self) {
+        let style = self.label_style.borrow();
+        for (annotation, widget) in self.annotations.iter().zip(&self.annotation_widgets) {
+            annotations::style_annotation(widget, annotation, &style);
+        }
+    }
+
+    // annotations are kept above the zones and below the cells
+    pub(crate) fn add_annotation(&mut self, desktop: &Fixed, annotation: MemoAnnotation, widget: gtk::Widget) {
+        desktop.put(&widget, 0f64, 0f64);
+        widget.insert_after(desktop, self.zone_widgets.last());
+        annotations::style_annotation(&widget, &annotation, &self.label_style.borrow());
+        self.annotations.push(annotation);
+        self.annotation_widgets.push(widget);
+        self.show_annotations(desktop);
+    }
+
+    pub(crate) fn delete_annotation(&mut self, desktop: &Fixed, index: usize) -> Option<Error> {
+        self.annotations.remove(index);
+        desktop.remove(&self.annotation_widgets.remove(index));
+        self.save_annotations()
+    }
+
+    pub(crate) fn annotation_index(&self, widget: &gtk::Widget) -> Option<usize> {
+        self.annotation_widgets.iter().position(|w| w == widget)
+    }
+
+    // an annotation moved to a negative position shifts the whole layout back
+    pub(crate) fn save_annotations(&mut self) -> Option<Error> {
+        if self.normalize_positions() {
+            return self.scan_positions_and_save_settings("", 0f64, 0f64);
+        }
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.annotations = self.annotations.clone();
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    // the connector layer goes above the zones and annotations
+    pub(crate) fn set_connector_layer(&mut self, desktop: &Fixed, layer: gtk::DrawingArea) {
+        desktop.put(&layer, 0f64, 0f64);
+        let below = self.annotation_widgets.last().cloned().or(self.zone_widgets.last().map(|z| z.clone().upcast()));
+        layer.insert_after(desktop, below.as_ref());
+        self.connector_layer = Some(layer);
+    }
+
+    pub(crate) fn refresh_connectors(&self) {
+        if let Some(layer) = &self.connector_layer {
+            layer.queue_draw();
+        }
+    }
+
+    pub(crate) fn add_connector(&mut self, from: &str, to: &str) -> Option<Error> {
+        let connector = MemoConnector { from: from.to_string(), to: to.to_string() };
+        if from == to || self.connectors.contains(&connector) {
+            return None;
+        }
+        self.connectors.push(connector);
+        self.save_connectors()
+    }
+
+    pub(crate) fn remove_connectors_of(&mut self, name: &str) -> Option<Error> {
+        self.connectors.retain(|c| c.from != name && c.to != name);
+        self.save_connectors()
+    }
+
+    fn save_connectors(&self) -> Option<Error> {
+        self.refresh_connectors();
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.connectors = self.connectors.clone();
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    pub(crate) fn is_shortcut(&self, name: &str) -> bool {
+        self.shortcuts.iter().any(|s| s.name == name)
+    }
+
+    // the cell of the shortcut is added by the caller
+    pub(crate) fn add_shortcut(&mut self, shortcut: MemoShortcut) -> Option<Error> {
+        self.shortcuts.push(shortcut);
+        self.save_shortcuts()
+    }
+
+    pub(crate) fn delete_shortcut(&mut self, name: &str) -> (Option<gtk::Box>, Option<Error>) {
+        self.shortcuts.retain(|s| s.name != name);
+        let (cell, err) = self.delete_cell(name.to_string());
+        if err.is_some() {
+            return (cell, err);
+        }
+        (cell, self.save_shortcuts())
+    }
+
+    // a file of the folder takes the name of a shortcut; the shortcut keeps its cell and settings under a free name it returns
+    pub(crate) fn move_shortcut_aside(&mut self, name: &str) -> (String, Option<Error>) {
+        let new_name = self.free_shortcut_name(name);
+        for shortcut in self.shortcuts.iter_mut().filter(|s| s.name == name) {
+            shortcut.name = new_name.clone();
+        }
+        // while loading, the file is there before the cell of the shortcut
+        if self.dir_items.get(name).is_some_and(|dir_item| dir_item.target.is_some()) {
+            if let Some(err) = self.rename_cell(name, new_name.as_str()) {
+                return (new_name, Some(err));
+            }
+        }
+        (new_name, self.save_shortcuts())
+    }
+
+    fn free_shortcut_name(&self, name: &str) -> String {
+        let is_taken = |candidate: &str| self.dir_items.contains_key(candidate) || self.is_shortcut(candidate)
+            || files::try_file((self.current_path.clone() + candidate).as_str());
+        let mut candidate = format!("{} (shortcut)", name);
+        let mut count = 2;
+        while is_taken(candidate.as_str()) {
+            candidate = format!("{} (shortcut {})", name, count);
+            count += 1;
+        }
+        candidate
+    }
+
+    // false for names held by a shortcut and for files not shown
+    pub(crate) fn shows_file(&self, name: &str) -> bool {
+        self.dir_items.get(name).is_some_and(|dir_item| dir_item.target.is_none())
+    }
+
+    fn save_shortcuts(&self) -> Option<Error> {
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.shortcuts = self.shortcuts.clone();
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    // a viewpoint with the same name is replaced
+    pub(crate) fn save_viewpoint(&mut self, viewpoint: MemoViewpoint) -> Option<Error> {
+        match self.viewpoints.iter_mut().find(|v| v.name == viewpoint.name) {
+            Some(existing) => *existing = viewpoint,
+            None => self.viewpoints.push(viewpoint),
+        }
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.viewpoints = self.viewpoints.clone();
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+
+    pub(crate) fn delete_viewpoint(&mut self, name: &str) -> Option<Error> {
+        self.viewpoints.retain(|v| v.name != name);
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.viewpoints = self.viewpoints.clone();
         files::save_settings(self.current_path.clone(), memo_folder)
     }
 
@@ -367,4 +1478,325 @@ impl MetaFolder {
         memo_folder.drilldown = self.drilldown;
         files::save_settings(self.current_path.clone(), memo_folder)
     }
+
+    pub(crate) fn set_drop_policy(&mut self, policy: DropPolicy) -> Option<Error> {
+        if self.drop_policy == policy {
+            return None;
+        }
+        self.drop_policy = policy;
+
+        let mut memo_folder = load_settings(self.current_path.clone());
+        memo_folder.drop_policy = self.drop_policy;
+        files::save_settings(self.current_path.clone(), memo_folder)
+    }
+}
+
+fn virtualize_when_idle(desktop: &Fixed) {
+    glib::idle_add_local_once(clone!(@weak desktop => move || {
+        // a folder drawn since
+        if desktop.root().is_none() {
+            return;
+        }
+        let ds = gtk_wrappers::get_application(&desktop);
+        let Ok(mut mf) = ds.imp().metafolder.try_borrow_mut() else {
+            // busy: tried again
+            virtualize_when_idle(&desktop);
+            return;
+        };
+        if mf.needs_virtualize.get() {
+            mf.virtualize(&desktop);
+        }
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use ignore::gitignore::GitignoreBuilder;
+
+    use crate::files::{Anchor, AnnotationKind, DirItem, IconStyle, load_settings, MemoAnnotation, MemoRect, MemoShortcut, MemoZone, PlacementStrategy};
+    use crate::spatial::Bounds;
+
+    use super::MetaFolder;
+
+    // a metafolder saving its settings in an empty folder of its own
+    fn metafolder_in_temp_folder(name: &str) -> MetaFolder {
+        let path = std::env::temp_dir().join(format!("metafolder-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let mut mf = MetaFolder::new();
+        mf.current_path = path.to_str().unwrap().to_string() + "/";
+        mf
+    }
+
+    #[test]
+    fn view_round_trip_restores_exact_positions() {
+        let mut mf = MetaFolder::new();
+        mf.origin.set((40f64, 25f64));
+        for (zoom_x, zoom_y, view_scale) in [(0, 0, 1f64), (130, 70, 1f64), (50, 200, 0.75f64), (90, 110, 1.6f64)] {
+            mf.zoom_x = zoom_x;
+            mf.zoom_y = zoom_y;
+            mf.view_scale = view_scale;
+            for (x, y) in [(0f64, 0f64), (37f64, 913f64), (1021f64, 3f64)] {
+                let (view_x, view_y) = mf.model_to_view(x, y);
+                let (model_x, model_y) = mf.view_to_model(view_x, view_y);
+                assert!((model_x - x).abs() < 1e-9 && (model_y - y).abs() < 1e-9, "({}, {}) came back as ({}, {})", x, y, model_x, model_y);
+            }
+        }
+        // back at 100% the view is the position moved by the origin, whatever was shown before
+        mf.zoom_x = 0;
+        mf.zoom_y = 0;
+        mf.view_scale = 1f64;
+        assert_eq!(mf.model_to_view(37f64, 913f64), (77f64, 938f64));
+    }
+
+    #[test]
+    fn view_applies_zoom_then_scale_then_origin() {
+        let mut mf = MetaFolder::new();
+        mf.origin.set((10f64, 20f64));
+        mf.zoom_x = 50;
+        mf.zoom_y = 200;
+        mf.view_scale = 2f64;
+        assert_eq!(mf.model_to_view(100f64, 100f64), (110f64, 420f64));
+    }
+
+    #[test]
+    fn normalizing_positions_keeps_the_view() {
+        let mut mf = MetaFolder::new();
+        mf.zoom_x = 130;
+        mf.zoom_y = 70;
+        mf.view_scale = 1.5f64;
+        mf.positions.borrow_mut().insert("a".to_string(), (-40f64, 10f64));
+        mf.positions.borrow_mut().insert("b".to_string(), (200f64, -15f64));
+        let before: Vec<(f64, f64)> = ["a", "b"].iter().map(|n| { let (x, y) = mf.positions.borrow()[*n]; mf.model_to_view(x, y) }).collect();
+        mf.normalize_positions();
+        assert_eq!(mf.positions.borrow()["a"], (0f64, 25f64));
+        for (name, (view_x, view_y)) in ["a", "b"].iter().zip(before) {
+            let (x, y) = mf.positions.borrow()[*name];
+            let (new_x, new_y) = mf.model_to_view(x, y);
+            assert!((new_x - view_x).abs() < 1e-9 && (new_y - view_y).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn normalizing_shifts_zones_with_the_cells() {
+        let mut mf = MetaFolder::new();
+        mf.zoom_x = 130;
+        mf.view_scale = 1.5f64;
+        mf.positions.borrow_mut().insert("a".to_string(), (-40.5f64, 10f64));
+        mf.zones.push(MemoZone { x: -60, y: 5, width: 100, height: 50, ..Default::default() });
+        let cell_before = mf.model_to_view(-40.5f64, 10f64);
+        let zone_before = mf.zone_view_bounds(&mf.zones[0]);
+        assert!(mf.normalize_positions());
+        // the zone was the furthest left: it lands on the edge, the cell keeps its distance to it
+        assert_eq!((mf.zones[0].x, mf.zones[0].y), (0, 5));
+        assert_eq!(mf.positions.borrow()["a"], (19.5f64, 10f64));
+        let (x, y) = mf.positions.borrow()["a"];
+        let cell_after = mf.model_to_view(x, y);
+        let zone_after = mf.zone_view_bounds(&mf.zones[0]);
+        assert!((cell_after.0 - cell_before.0).abs() < 1e-9 && (cell_after.1 - cell_before.1).abs() < 1e-9);
+        assert!((zone_after.x - zone_before.x).abs() < 1e-9 && (zone_after.w - zone_before.w).abs() < 1e-9);
+        assert!(!mf.normalize_positions());
+    }
+
+    #[test]
+    fn normalizing_shifts_annotations_with_the_cells() {
+        let mut mf = MetaFolder::new();
+        mf.zoom_y = 70;
+        mf.positions.borrow_mut().insert("a".to_string(), (30f64, 40f64));
+        mf.annotations.push(MemoAnnotation { kind: AnnotationKind::Note, x: 10, y: 60, width: 160, height: 120, ..Default::default() });
+        // an arrow pointing up from its start
+        mf.annotations.push(MemoAnnotation { kind: AnnotationKind::Arrow, x: 100, y: 20, width: 50, height: -45, ..Default::default() });
+        let before: Vec<(f64, f64)> = mf.annotations.iter().map(|a| mf.model_to_view(a.x as f64, a.y as f64)).collect();
+        assert!(mf.normalize_positions());
+        assert_eq!(mf.positions.borrow()["a"], (30f64, 65f64));
+        assert_eq!((mf.annotations[0].x, mf.annotations[0].y), (10, 85));
+        assert_eq!((mf.annotations[1].x, mf.annotations[1].y, mf.annotations[1].height), (100, 45, -45));
+        for (annotation, (view_x, view_y)) in mf.annotations.iter().zip(before) {
+            let (x, y) = mf.model_to_view(annotation.x as f64, annotation.y as f64);
+            assert!((x - view_x).abs() < 1e-9 && (y - view_y).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn is_shown_follows_hidden_files_and_ignore_rules() {
+        let mut mf = MetaFolder::new();
+        let mut builder = GitignoreBuilder::new("/folder/");
+        builder.add_line(None, "*.log").unwrap();
+        builder.add_line(None, "build/").unwrap();
+        mf.ignore_rules = builder.build().unwrap();
+        let item = |name: &str, is_hidden: bool, is_dir: bool| DirItem { name: name.to_string(), is_hidden, is_dir, ..Default::default() };
+        assert!(mf.is_shown(&item("notes.txt", false, false)));
+        assert!(!mf.is_shown(&item("run.log", false, false)));
+        assert!(!mf.is_shown(&item("build", false, true)));
+        assert!(mf.is_shown(&item("build", false, false)));
+        assert!(!mf.is_shown(&item(".profile", true, false)));
+        mf.show_hidden = true;
+        assert!(mf.is_shown(&item(".profile", true, false)));
+        // the settings of the folder never show
+        assert!(!mf.is_shown(&item(".metafolder", true, false)));
+    }
+
+    #[test]
+    fn saving_keeps_the_settings_of_hidden_and_ignored_files() {
+        let mut mf = metafolder_in_temp_folder("unshown-settings");
+        mf.dir_items.insert("notes.txt".to_string(), DirItem { name: "notes.txt".to_string(), ..Default::default() });
+        mf.unshown.insert(".profile".to_string());
+        mf.unshown.insert("run.log".to_string());
+        for (name, position) in [("notes.txt", (10f64, 20f64)), (".profile", (30f64, 40f64)), ("run.log", (50f64, 60f64)), ("gone.txt", (70f64, 80f64))] {
+            mf.positions.borrow_mut().insert(name.to_string(), position);
+        }
+        mf.anchors.borrow_mut().insert(".profile".to_string(), (Anchor::TopRight, 5, 6));
+        mf.custom_icons.insert("run.log".to_string(), "text-x-script".to_string());
+        mf.forget_missing_files();
+        assert!(mf.scan_positions_and_save_settings("", 0f64, 0f64).is_none());
+        let icons = load_settings(mf.current_path.clone()).icons;
+        let mut names: Vec<&String> = icons.keys().collect();
+        names.sort();
+        assert_eq!(names, [".profile", "notes.txt", "run.log"]);
+        // a hidden file keeps its place and anchor, an ignored one its icon
+        let profile = &icons[".profile"];
+        assert_eq!((profile.position_x, profile.position_y, profile.anchor, profile.anchor_offset_x), (30, 40, Anchor::TopRight, 5));
+        assert_eq!(icons["run.log"].custom_icon.as_deref(), Some("text-x-script"));
+        fs::remove_dir_all(mf.current_path).unwrap();
+    }
+
+    #[test]
+    fn anchored_cells_keep_their_distance_from_the_edges() {
+        let visible = Bounds::new(200f64, 100f64, 800f64, 600f64);
+        let cell = Bounds::new(850f64, 620f64, 100f64, 60f64);
+        assert_eq!(MetaFolder::anchor_offsets(Anchor::BottomRight, &cell, &visible), (50, 20));
+        assert_eq!(MetaFolder::anchor_offsets(Anchor::TopLeft, &cell, &visible), (650, 520));
+        // after scrolling, the cell is put back at the same distance from the bottom right corner
+        let scrolled = Bounds::new(500f64, 300f64, 800f64, 600f64);
+        assert_eq!(MetaFolder::anchored_position(Anchor::BottomRight, (50, 20), &cell, &scrolled), (1150f64, 820f64));
+        // an edge anchor leaves the other axis alone
+        assert_eq!(MetaFolder::anchored_position(Anchor::Top, (50, 20), &cell, &scrolled), (850f64, 320f64));
+        assert_eq!(MetaFolder::anchored_position(Anchor::Right, (50, 20), &cell, &scrolled), (1150f64, 620f64));
+        // resolving the offsets where they were taken puts the cell where it was
+        for anchor in [Anchor::Left, Anchor::Bottom, Anchor::TopRight, Anchor::BottomLeft] {
+            let offsets = MetaFolder::anchor_offsets(anchor, &cell, &visible);
+            assert_eq!(MetaFolder::anchored_position(anchor, offsets, &cell, &visible), (cell.x, cell.y));
+        }
+    }
+
+    #[test]
+    fn shortcuts_make_way_for_files_of_the_same_name() {
+        let mut mf = metafolder_in_temp_folder("shortcut-names");
+        mf.shortcuts.push(MemoShortcut { name: "notes".to_string(), target: "/tmp/notes".to_string() });
+        mf.shortcuts.push(MemoShortcut { name: "notes (shortcut 2)".to_string(), target: "https://example.org".to_string() });
+        fs::write(mf.current_path.clone() + "notes (shortcut)", "").unwrap();
+        // the first free name is neither a file of the folder nor another shortcut
+        assert_eq!(mf.free_shortcut_name("notes"), "notes (shortcut 3)");
+        let (new_name, err) = mf.move_shortcut_aside("notes");
+        assert!(err.is_none());
+        assert_eq!(new_name, "notes (shortcut 3)");
+        assert!(!mf.is_shortcut("notes"));
+        let saved: Vec<String> = load_settings(mf.current_path.clone()).shortcuts.into_iter().map(|s| s.name).collect();
+        assert_eq!(saved, ["notes (shortcut 3)", "notes (shortcut 2)"]);
+        fs::remove_dir_all(mf.current_path).unwrap();
+    }
+
+    #[test]
+    fn icon_styles_override_the_looks_of_the_folder() {
+        let mf = MetaFolder::new();
+        mf.label_style.borrow_mut().size = "large".to_string();
+        mf.label_style.borrow_mut().color = "#ffffff".to_string();
+        let label = "<span font_size=\"small\" font_weight=\"bold\"  color=\"white\">notes.txt</span>";
+        // nothing overridden: all looks of the folder
+        assert_eq!(mf.styled_label(label, &IconStyle::default()),
+                   "<span font_size=\"large\" font_weight=\"bold\"  color=\"#ffffff\">notes.txt</span>");
+        let icon_style = IconStyle { color: Some("#ff0000".to_string()), bold: Some(false), ..Default::default() };
+        assert_eq!(mf.styled_label(label, &icon_style),
+                   "<span font_size=\"large\" font_weight=\"normal\"  color=\"#ff0000\">notes.txt</span>");
+        // a folder without bold labels leaves a bold icon bold
+        mf.label_style.borrow_mut().bold = false;
+        let icon_style = IconStyle { bold: Some(true), ..Default::default() };
+        assert_eq!(mf.styled_label(label, &icon_style),
+                   "<span font_size=\"large\" font_weight=\"bold\"  color=\"#ffffff\">notes.txt</span>");
+    }
+
+    // a file of the given type with a cell at bounds, if any
+    fn add_item(mf: &mut MetaFolder, name: &str, mime_type: &str, modified: i64, bounds: Option<Bounds>) {
+        mf.dir_items.insert(name.to_string(), DirItem { name: name.to_string(), mime_type: mime_type.to_string(), modified, ..Default::default() });
+        if let Some(bounds) = bounds {
+            mf.spatial_index.borrow_mut().insert(name, bounds);
+        }
+    }
+
+    #[test]
+    fn new_cells_are_placed_by_name_or_by_date() {
+        let mut mf = MetaFolder::new();
+        add_item(&mut mf, "b.txt", "text/plain", 300, None);
+        add_item(&mut mf, "a.txt", "text/plain", 200, None);
+        add_item(&mut mf, "c.txt", "text/plain", 200, None);
+        mf.added_cells.extend(["b.txt", "a.txt", "c.txt"].map(String::from));
+        mf.placement = PlacementStrategy::EndByName;
+        assert_eq!(mf.sorted_added_cells(), ["a.txt", "b.txt", "c.txt"]);
+        // files modified at the same time stay in name order
+        mf.placement = PlacementStrategy::EndByDate;
+        assert_eq!(mf.sorted_added_cells(), ["a.txt", "c.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn same_type_anchor_is_the_cell_closest_to_the_others() {
+        let mut mf = MetaFolder::new();
+        add_item(&mut mf, "new.png", "image/png", 0, None);
+        add_item(&mut mf, "left.png", "image/png", 0, Some(Bounds::new(0f64, 0f64, 50f64, 50f64)));
+        add_item(&mut mf, "middle.png", "image/png", 0, Some(Bounds::new(110f64, 0f64, 50f64, 50f64)));
+        add_item(&mut mf, "right.png", "image/png", 0, Some(Bounds::new(300f64, 0f64, 50f64, 50f64)));
+        add_item(&mut mf, "notes.txt", "text/plain", 0, Some(Bounds::new(120f64, 0f64, 50f64, 50f64)));
+        assert_eq!(mf.same_type_anchor("new.png"), Some(Bounds::new(110f64, 0f64, 50f64, 50f64)));
+        // the only file of its type has nothing to go next to
+        add_item(&mut mf, "song.ogg", "audio/ogg", 0, None);
+        assert_eq!(mf.same_type_anchor("song.ogg"), None);
+    }
+
+    #[test]
+    fn layout_bottom_leaves_out_new_cells() {
+        let mut mf = MetaFolder::new();
+        assert_eq!(mf.layout_bottom(), 0f64);
+        add_item(&mut mf, "placed.txt", "text/plain", 0, Some(Bounds::new(0f64, 100f64, 50f64, 60f64)));
+        add_item(&mut mf, "new.txt", "text/plain", 0, Some(Bounds::new(0f64, 400f64, 50f64, 60f64)));
+        mf.added_cells.insert("new.txt".to_string());
+        assert_eq!(mf.layout_bottom(), 160f64);
+    }
+
+    #[test]
+    fn saved_positions_are_rounded_under_a_scaled_view() {
+        let mut mf = metafolder_in_temp_folder("scaled-positions");
+        mf.view_scale = 0.75f64;
+        mf.origin.set((10f64, 10f64));
+        mf.positions.borrow_mut().insert("dropped.txt".to_string(), (0f64, 0f64));
+        mf.positions.borrow_mut().insert("kept.txt".to_string(), (40.7f64, 60.5f64));
+        // dropped at a view position that is 121.33 unzoomed pixels from the origin
+        assert!(mf.scan_positions_and_save_settings("dropped.txt", 101f64, 101f64).is_none());
+        let icons = load_settings(mf.current_path.clone()).icons;
+        let reloaded = |name: &str| (icons[name].position_x as f64, icons[name].position_y as f64);
+        assert_eq!(reloaded("dropped.txt"), (121f64, 121f64));
+        assert_eq!(reloaded("kept.txt"), (41f64, 61f64));
+        // a reloaded cell is shown less than half a scaled pixel from where it was dropped
+        let (view_x, view_y) = mf.model_to_view(reloaded("dropped.txt").0, reloaded("dropped.txt").1);
+        assert!((view_x - 101f64).abs() <= 0.375f64 && (view_y - 101f64).abs() <= 0.375f64);
+        fs::remove_dir_all(mf.current_path).unwrap();
+    }
+
+    #[test]
+    fn inbox_stays_on_the_same_part_of_the_folder() {
+        let mut mf = MetaFolder::new();
+        mf.zoom_x = 150;
+        mf.zoom_y = 150;
+        mf.view_scale = 0.5f64;
+        mf.origin.set((30f64, 10f64));
+        let inbox = mf.inbox_from_view(&Bounds::new(105f64, 85f64, 225f64, 150f64));
+        assert_eq!(inbox, MemoRect { x: 100, y: 100, width: 300, height: 200 });
+        assert_eq!(mf.inbox_view_bounds(&inbox), Bounds::new(105f64, 85f64, 225f64, 150f64));
+        // at 100% it covers the unzoomed area
+        mf.zoom_x = 0;
+        mf.zoom_y = 0;
+        mf.view_scale = 1f64;
+        mf.origin.set((0f64, 0f64));
+        assert_eq!(mf.inbox_view_bounds(&inbox), Bounds::new(100f64, 100f64, 300f64, 200f64));
+    }
 }
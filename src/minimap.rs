@@ -0,0 +1,111 @@
+use gtk::{Align, DrawingArea, GestureDrag, Overlay, ScrolledWindow};
+use gtk::glib;
+use gtk::glib::clone;
+use gtk::prelude::{AdjustmentExt, Cast, DrawingAreaExtManual, EventControllerExt, GestureDragExt, IsA, ToggleButtonExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::gtk_wrappers;
+
+const MINIMAP_WIDTH: i32 = 200;
+const MINIMAP_HEIGHT: i32 = 150;
+
+// overlays the scrolled desktop with a small map of all cells and the part in view; clicking or dragging on it scrolls there
+pub(crate) fn make_minimap(scrolled_window: &ScrolledWindow) -> (Overlay, DrawingArea) {
+    let minimap = DrawingArea::builder()
+        .content_width(MINIMAP_WIDTH)
+        .content_height(MINIMAP_HEIGHT)
+        .halign(Align::End)
+        .valign(Align::End)
+        .margin_end(15)
+        .margin_bottom(15)
+        .build();
+    minimap.set_tooltip_text(Some("click to go there"));
+    minimap.set_draw_func(clone!(@weak scrolled_window => move |area, cr, width, height| {
+        draw_minimap(area, &scrolled_window, cr, width as f64, height as f64);
+    }));
+
+    let navigate = GestureDrag::new();
+    navigate.connect_drag_begin(clone!(@weak scrolled_window => move |gesture, x, y| {
+        let Some(area) = gesture.widget().downcast::<DrawingArea>().ok() else { return };
+        scroll_to(&area, &scrolled_window, x, y);
+    }));
+    navigate.connect_drag_update(clone!(@weak scrolled_window => move |gesture, offset_x, offset_y| {
+        let Some((start_x, start_y)) = gesture.start_point() else { return };
+        let Some(area) = gesture.widget().downcast::<DrawingArea>().ok() else { return };
+        scroll_to(&area, &scrolled_window, start_x + offset_x, start_y + offset_y);
+    }));
+    minimap.add_controller(navigate);
+
+    for adjustment in [scrolled_window.hadjustment(), scrolled_window.vadjustment()] {
+        adjustment.connect_value_changed(clone!(@weak minimap => move |_| minimap.queue_draw()));
+        adjustment.connect_changed(clone!(@weak minimap => move |_| minimap.queue_draw()));
+    }
+
+    let overlay = Overlay::builder().child(scrolled_window).hexpand(true).build();
+    overlay.add_overlay(&minimap);
+    (overlay, minimap)
+}
+
+// minimap pixels per desktop pixel, the whole desktop always fits
+fn minimap_scale(scrolled_window: &ScrolledWindow, width: f64, height: f64) -> f64 {
+    let desktop_width = scrolled_window.hadjustment().upper().max(1f64);
+    let desktop_height = scrolled_window.vadjustment().upper().max(1f64);
+    (width / desktop_width).min(height / desktop_height)
+}
+
+fn draw_minimap(area: &DrawingArea, scrolled_window: &ScrolledWindow, cr: &gtk::cairo::Context, width: f64, height: f64) {
+    let scale = minimap_scale(scrolled_window, width, height);
+    cr.set_source_rgba(0.1, 0.1, 0.1, 0.6);
+    cr.rectangle(0f64, 0f64, width, height);
+    let _ = cr.fill();
+
+    let ds = gtk_wrappers::get_application(area);
+    if let Ok(mf) = ds.imp().metafolder.try_borrow() {
+        cr.set_source_rgba(1.0, 1.0, 1.0, 0.8);
+        for b in mf.spatial_index.borrow().all() {
+            cr.rectangle(b.x * scale, b.y * scale, (b.w * scale).max(1f64), (b.h * scale).max(1f64));
+        }
+        let _ = cr.fill();
+    }
+
+    let h = scrolled_window.hadjustment();
+    let v = scrolled_window.vadjustment();
+    cr.set_source_rgba(1.0, 0.85, 0.0, 1.0);
+    cr.set_line_width(2f64);
+    cr.rectangle(h.value() * scale, v.value() * scale, h.page_size() * scale, v.page_size() * scale);
+    let _ = cr.stroke();
+}
+
+// centers the visible area on the desktop point under (x, y) of the minimap
+fn scroll_to(area: &DrawingArea, scrolled_window: &ScrolledWindow, x: f64, y: f64) {
+    let scale = minimap_scale(scrolled_window, area.width() as f64, area.height() as f64);
+    let h = scrolled_window.hadjustment();
+    let v = scrolled_window.vadjustment();
+    h.set_value(x / scale - h.page_size() / 2f64);
+    v.set_value(y / scale - v.page_size() / 2f64);
+}
+
+pub(crate) fn refresh_minimap(w: &impl IsA<gtk::Widget>) {
+    if w.root().is_none() {
+        return;
+    }
+    let ds = gtk_wrappers::get_application(w);
+    let binding = ds.imp().minimap.borrow();
+    if let Some(minimap) = binding.as_ref() {
+        minimap.queue_draw();
+    }
+}
+
+pub(crate) fn show_minimap(w: &impl IsA<gtk::Widget>, show: bool) {
+    let ds = gtk_wrappers::get_application(w);
+    let binding = ds.imp().minimap.borrow();
+    if let Some(minimap) = binding.as_ref() {
+        minimap.set_visible(show);
+    }
+}
+
+pub(crate) fn minimap_toggle(w: &impl IsA<gtk::Widget>) -> bool {
+    let ds = gtk_wrappers::get_application(w);
+    let binding = ds.imp().minimap_button.borrow();
+    binding.as_ref().map(|b| b.is_active()).unwrap_or(false)
+}
@@ -0,0 +1,59 @@
+use gtk::{Button, DropDown, Label, Popover};
+use gtk::glib;
+use gtk::glib::clone;
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::prelude::{BoxExt, ButtonExt, PopoverExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::{gtk_wrappers, PLACEMENTS};
+use crate::files::PlacementStrategy;
+use crate::gtk_wrappers::{alert, get_visible_area};
+
+pub(crate) fn make_placement() -> (Popover, DropDown) {
+    let container = gtk::Box::builder().orientation(Vertical).spacing(10).build();
+    let popover = Popover::builder().build();
+    popover.set_child(Some(&container));
+
+    let strategy_box = gtk::Box::builder().orientation(Horizontal).spacing(10).build();
+    strategy_box.append(&Label::builder().label("place new files at").build());
+    let placement_dropdown = DropDown::from_strings(PLACEMENTS);
+    placement_dropdown.set_tooltip_text(Some("where files appearing in the folder are placed - placed icons are never moved"));
+    placement_dropdown.connect_selected_notify(|dd| {
+        let ds = gtk_wrappers::get_application(dd);
+        let result = ds.imp().metafolder.borrow_mut().set_placement(PlacementStrategy::from_index(dd.selected()));
+        if let Some(err) = result {
+            alert(dd, "folder settings could not be saved".to_string(), err.to_string());
+        }
+    });
+    strategy_box.append(&placement_dropdown);
+    container.append(&strategy_box);
+
+    let inbox_box = gtk::Box::builder().orientation(Horizontal).spacing(10).build();
+    let set_inbox = Button::builder().label("visible area is inbox").build();
+    set_inbox.set_tooltip_text(Some("use the part of the folder currently in view as inbox for new files"));
+    set_inbox.connect_clicked(clone!(@weak popover => move |b| {
+        let visible = get_visible_area(b);
+        let ds = gtk_wrappers::get_application(b);
+        let mut mf = ds.imp().metafolder.borrow_mut();
+        let inbox = mf.inbox_from_view(&visible);
+        let result = mf.set_inbox(Some(inbox));
+        drop(mf);
+        if let Some(err) = result {
+            alert(b, "folder settings could not be saved".to_string(), err.to_string());
+        }
+        popover.popdown();
+    }));
+    inbox_box.append(&set_inbox);
+    let clear_inbox = Button::builder().label("clear inbox").build();
+    clear_inbox.connect_clicked(|b| {
+        let ds = gtk_wrappers::get_application(b);
+        let result = ds.imp().metafolder.borrow_mut().set_inbox(None);
+        if let Some(err) = result {
+            alert(b, "folder settings could not be saved".to_string(), err.to_string());
+        }
+    });
+    inbox_box.append(&clear_inbox);
+    container.append(&inbox_box);
+
+    (popover, placement_dropdown)
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use gtk::{Button, Entry, Label, Popover};
+use gtk::glib;
+use gtk::glib::clone;
+use gtk::Orientation::Vertical;
+use gtk::prelude::{BoxExt, ButtonExt, CancellableExt, EditableExt, FixedExt, IsA, PopoverExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::{files, gtk_wrappers};
+use crate::files::MemoShortcut;
+use crate::gtk_wrappers::{alert, get_desktop};
+
+// shortcuts to files, folders and URLs that are not in the folder; they live only in its settings
+pub(crate) fn make_shortcuts_menu() -> Popover {
+    let container = gtk::Box::builder().orientation(Vertical).spacing(5).build();
+    let popover = Popover::builder().child(&container).build();
+    let target_entry = Entry::builder().placeholder_text("path or URL").width_chars(30).build();
+    container.append(&target_entry);
+    let name_entry = Entry::builder().placeholder_text("name (optional)").build();
+    container.append(&name_entry);
+    let add = Button::builder().label("add shortcut").build();
+    add.connect_clicked(clone!(@weak target_entry, @weak name_entry, @weak popover => move |b| {
+        let target = normalize_target(target_entry.text().trim());
+        if target.is_empty() {
+            return;
+        }
+        let name = match name_entry.text().trim() {
+            "" => default_name(target.as_str()),
+            name => name.to_string(),
+        };
+        if add_shortcut(b, MemoShortcut { name, target }) {
+            target_entry.set_text("");
+            name_entry.set_text("");
+            popover.popdown();
+        }
+    }));
+    container.append(&add);
+    container.append(&Label::new(Some("right click a shortcut to remove it")));
+    popover
+}
+
+// expands ~ and drops trailing slashes of paths; URLs are kept as they are
+fn normalize_target(target: &str) -> String {
+    if target.contains("://") {
+        return target.to_string();
+    }
+    let target = match target.strip_prefix('~') {
+        Some(rest) => files::home_path() + rest,
+        None => target.to_string(),
+    };
+    match target.trim_end_matches('/') {
+        "" if !target.is_empty() => "/".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+// the host of a URL, the file name of a path
+fn default_name(target: &str) -> String {
+    if let Some((_, rest)) = target.split_once("://") {
+        return rest.split('/').next().unwrap_or(rest).to_string();
+    }
+    Path::new(target).file_name().and_then(|n| n.to_str()).unwrap_or(target).to_string()
+}
+
+// places the cell of a new shortcut like a new file, but as placed by the user; false when the name is taken
+fn add_shortcut(w: &impl IsA<gtk::Widget>, shortcut: MemoShortcut) -> bool {
+    let desktop = get_desktop(w);
+    let ds = gtk_wrappers::get_application(w);
+    let mf = ds.imp().metafolder.borrow();
+    let name = shortcut.name.clone();
+    // hidden and ignored files count too: they could be shown at any time
+    if mf.dir_items.contains_key(&name) || files::try_file((mf.get_current_path() + name.as_str()).as_str()) {
+        drop(mf);
+        alert(w, "shortcut not added".to_string(), format!("there is already an icon or a file named {}", name));
+        return false;
+    }
+    let cancellable = mf.load_cancellable.clone();
+    drop(mf);
+    // the target is read in the background, it may be on a slow mount
+    glib::spawn_future_local(async move {
+        let dir_item = files::get_shortcut_info(shortcut.clone()).await;
+        if cancellable.is_cancelled() {
+            return;
+        }
+        let ds = gtk_wrappers::get_application(&desktop);
+        let mut mf = ds.imp().metafolder.borrow_mut();
+        // a file arrived meanwhile
+        if mf.dir_items.contains_key(&name) {
+            drop(mf);
+            alert(&desktop, "shortcut not added".to_string(), format!("there is already an icon or a file named {}", name));
+            return;
+        }
+        let mut result = mf.add_shortcut(shortcut);
+        mf.add_cell(&desktop, dir_item);
+        mf.place_new_cell(&desktop, name.as_str());
+        mf.clear_added_flag(name.clone());
+        if result.is_none() {
+            result = mf.scan_positions_and_save_settings("", 0f64, 0f64);
+        }
+        drop(mf);
+        if let Some(err) = result {
+            alert(&desktop, "folder settings could not be saved".to_string(), err.to_string());
+        }
+    });
+    true
+}
+
+// the shortcut section of the cell menu, only for the cells of shortcuts
+pub(crate) fn make_shortcut_section(name: String, popover: &Popover) -> gtk::Box {
+    let section = gtk::Box::builder().orientation(Vertical).spacing(5).build();
+    let remove = Button::builder().label("remove shortcut").build();
+    remove.set_tooltip_text(Some("the target itself is left alone"));
+    remove.connect_clicked(clone!(@weak popover => move |b| {
+        let desktop = get_desktop(b);
+        let ds = gtk_wrappers::get_application(b);
+        let (cell, result) = ds.imp().metafolder.borrow_mut().delete_shortcut(name.as_str());
+        popover.popdown();
+        if let Some(cell) = cell {
+            desktop.remove(&cell);
+        }
+        if let Some(err) = result {
+            alert(b, "folder settings could not be saved".to_string(), err.to_string());
+        }
+    }));
+    section.append(&remove);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_name, normalize_target};
+    use crate::files;
+
+    #[test]
+    fn normalize_target_expands_home_and_trims_slashes() {
+        assert_eq!(normalize_target("https://example.org/a/"), "https://example.org/a/");
+        assert_eq!(normalize_target("/tmp/projects//"), "/tmp/projects");
+        assert_eq!(normalize_target("/"), "/");
+        assert_eq!(normalize_target("~/notes"), files::home_path() + "/notes");
+    }
+
+    #[test]
+    fn default_name_is_the_host_or_the_file_name() {
+        assert_eq!(default_name("https://example.org/a/b"), "example.org");
+        assert_eq!(default_name("/tmp/projects"), "projects");
+        assert_eq!(default_name("/"), "/");
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::{HashMap, HashSet};
+
+const BUCKET_SIZE: f64 = 128f64;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct Bounds {
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) w: f64,
+    pub(crate) h: f64,
+}
+
+impl Bounds {
+    pub(crate) fn new(x: f64, y: f64, w: f64, h: f64) -> Self {
+        Bounds { x, y, w, h }
+    }
+
+    // two rectangles overlap when they are closer than spacing on both axes
+    pub(crate) fn overlaps(&self, other: &Bounds, spacing: f64) -> bool {
+        self.x < other.x + other.w + spacing &&
+            other.x < self.x + self.w + spacing &&
+            self.y < other.y + other.h + spacing &&
+            other.y < self.y + self.h + spacing
+    }
+
+    fn buckets(&self, spacing: f64) -> Vec<(i64, i64)> {
+        let min_col = ((self.x - spacing) / BUCKET_SIZE).floor() as i64;
+        let max_col = ((self.x + self.w + spacing) / BUCKET_SIZE).floor() as i64;
+        let min_row = ((self.y - spacing) / BUCKET_SIZE).floor() as i64;
+        let max_row = ((self.y + self.h + spacing) / BUCKET_SIZE).floor() as i64;
+        let mut buckets = Vec::new();
+        for col in min_col..=max_col {
+            for row in min_row..=max_row {
+                buckets.push((col, row));
+            }
+        }
+        buckets
+    }
+}
+
+// Uniform grid over cell bounds, so that collision checks only look at nearby cells
+#[derive(Debug, Default)]
+pub(crate) struct SpatialIndex {
+    bounds: HashMap<String, Bounds>,
+    buckets: HashMap<(i64, i64), HashSet<String>>,
+}
+
+impl SpatialIndex {
+    pub(crate) fn clear(&mut self) {
+        self.bounds.clear();
+        self.buckets.clear();
+    }
+
+    pub(crate) fn insert(&mut self, name: &str, b: Bounds) {
+        self.remove(name);
+        for bucket in b.buckets(0f64) {
+            self.buckets.entry(bucket).or_default().insert(name.to_string());
+        }
+        self.bounds.insert(name.to_string(), b);
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) -> Option<Bounds> {
+        let b = self.bounds.remove(name)?;
+        for bucket in b.buckets(0f64) {
+            if let Some(names) = self.buckets.get_mut(&bucket) {
+                names.remove(name);
+                if names.is_empty() {
+                    self.buckets.remove(&bucket);
+                }
+            }
+        }
+        Some(b)
+    }
+
+    pub(crate) fn rename(&mut self, old_name: &str, new_name: &str) {
+        if let Some(b) = self.remove(old_name) {
+            self.insert(new_name, b);
+        }
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<Bounds> {
+        self.bounds.get(name).copied()
+    }
+
+    pub(crate) fn all(&self) -> impl Iterator<Item = &Bounds> {
+        self.bounds.values()
+    }
+
+    // right and bottom edges of the area covered by all cells
+    pub(crate) fn extent(&self) -> (f64, f64) {
+        self.bounds.values().fold((0f64, 0f64), |(right, bottom), b| (right.max(b.x + b.w), bottom.max(b.y + b.h)))
+    }
+
+    // returns the name of a cell (other than exclude) overlapping b, the lowest one in name order to be deterministic
+    pub(crate) fn overlapping(&self, exclude: &str, b: &Bounds, spacing: f64) -> Option<String> {
+        let mut found: Option<&String> = None;
+        for bucket in b.buckets(spacing) {
+            let Some(names) = self.buckets.get(&bucket) else { continue };
+            for name in names {
+                if name == exclude {
+                    continue;
+                }
+                if self.bounds[name].overlaps(b, spacing) && (found.is_none() || found.unwrap() > name) {
+                    found = Some(name);
+                }
+            }
+        }
+        found.cloned()
+    }
+
+    // names of all cells overlapping b, in no particular order
+    pub(crate) fn overlapping_all(&self, b: &Bounds, spacing: f64) -> Vec<String> {
+        let mut found = HashSet::new();
+        for bucket in b.buckets(spacing) {
+            let Some(names) = self.buckets.get(&bucket) else { continue };
+            for name in names {
+                if self.bounds[name].overlaps(b, spacing) {
+                    found.insert(name.clone());
+                }
+            }
+        }
+        found.into_iter().collect()
+    }
+
+    // row-major sweep from (origin_x, origin_y) rightwards up to max_x; skips over any cell in the way, so it never overlaps
+    pub(crate) fn find_free_spot(&self, origin_x: f64, origin_y: f64, w: f64, h: f64, spacing: f64, max_x: f64) -> (f64, f64) {
+        self.sweep(origin_x, origin_y, w, h, spacing, (max_x, f64::INFINITY)).unwrap_or((origin_x, origin_y))
+    }
+
+    // the same sweep kept inside area; None when the cell fits nowhere in it
+    pub(crate) fn find_free_spot_in(&self, area: &Bounds, w: f64, h: f64, spacing: f64) -> Option<(f64, f64)> {
+        if w > area.w {
+            return None;
+        }
+        self.sweep(area.x, area.y, w, h, spacing, (area.x + area.w, area.y + area.h))
+    }
+
+    fn sweep(&self, origin_x: f64, origin_y: f64, w: f64, h: f64, spacing: f64, (max_x, max_y): (f64, f64)) -> Option<(f64, f64)> {
+        let row_step = (h / 2f64).max(1f64);
+        let mut y = origin_y;
+        while y + h <= max_y {
+            let mut x = origin_x;
+            while x == origin_x || x + w <= max_x {
+                let candidate = Bounds::new(x, y, w, h);
+                match self.overlapping("", &candidate, spacing) {
+                    None => return Some((x, y)),
+                    Some(other) => {
+                        let o = self.bounds[&other];
+                        x = o.x + o.w + spacing;
+                    }
+                }
+            }
+            y += row_step;
+        }
+        None
+    }
+
+    // searches rings of growing radius around b and returns the closest position where it fits
+    pub(crate) fn find_free_spot_near(&self, exclude: &str, b: &Bounds, spacing: f64) -> (f64, f64) {
+        let step = (b.w.max(b.h) / 2f64).max(1f64);
+        for ring in 1..50i32 {
+            let mut candidates: Vec<(f64, f64)> = Vec::new();
+            for i in -ring..=ring {
+                for j in -ring..=ring {
+                    if i.abs() != ring && j.abs() != ring {
+                        continue;
+                    }
+                    let cx = b.x + i as f64 * step;
+                    let cy = b.y + j as f64 * step;
+                    if cx < 0f64 || cy < 0f64 {
+                        continue;
+                    }
+                    candidates.push((cx, cy));
+                }
+            }
+            candidates.sort_by(|a, c| {
+                let da = (a.0 - b.x).powi(2) + (a.1 - b.y).powi(2);
+                let dc = (c.0 - b.x).powi(2) + (c.1 - b.y).powi(2);
+                da.total_cmp(&dc)
+            });
+            for (cx, cy) in candidates {
+                if self.overlapping(exclude, &Bounds::new(cx, cy, b.w, b.h), spacing).is_none() {
+                    return (cx, cy);
+                }
+            }
+        }
+        (b.x, b.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bounds, SpatialIndex};
+
+    fn index_of(cells: &[(&str, Bounds)]) -> SpatialIndex {
+        let mut index = SpatialIndex::default();
+        for (name, b) in cells {
+            index.insert(name, *b);
+        }
+        index
+    }
+
+    #[test]
+    fn overlaps_counts_spacing_on_both_axes() {
+        let a = Bounds::new(0f64, 0f64, 100f64, 100f64);
+        assert!(a.overlaps(&Bounds::new(50f64, 50f64, 100f64, 100f64), 0f64));
+        assert!(!a.overlaps(&Bounds::new(100f64, 0f64, 100f64, 100f64), 0f64));
+        assert!(a.overlaps(&Bounds::new(105f64, 0f64, 100f64, 100f64), 10f64));
+        // close on one axis only
+        assert!(!a.overlaps(&Bounds::new(105f64, 300f64, 100f64, 100f64), 10f64));
+    }
+
+    #[test]
+    fn overlapping_returns_the_lowest_name_and_skips_exclude() {
+        let index = index_of(&[
+            ("b", Bounds::new(0f64, 0f64, 100f64, 100f64)),
+            ("a", Bounds::new(50f64, 0f64, 100f64, 100f64)),
+            ("far", Bounds::new(1000f64, 1000f64, 100f64, 100f64)),
+        ]);
+        let probe = Bounds::new(60f64, 10f64, 20f64, 20f64);
+        assert_eq!(index.overlapping("", &probe, 0f64), Some("a".to_string()));
+        assert_eq!(index.overlapping("a", &probe, 0f64), Some("b".to_string()));
+        assert_eq!(index.overlapping("", &Bounds::new(500f64, 500f64, 10f64, 10f64), 0f64), None);
+        let mut all = index.overlapping_all(&probe, 0f64);
+        all.sort();
+        assert_eq!(all, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn overlapping_finds_cells_across_buckets() {
+        // larger than a bucket, so it spans several of them
+        let index = index_of(&[("big", Bounds::new(0f64, 0f64, 600f64, 600f64))]);
+        assert_eq!(index.overlapping("", &Bounds::new(500f64, 500f64, 10f64, 10f64), 0f64), Some("big".to_string()));
+    }
+
+    #[test]
+    fn find_free_spot_skips_cells_and_wraps_rows() {
+        let empty = SpatialIndex::default();
+        assert_eq!(empty.find_free_spot(10f64, 20f64, 50f64, 50f64, 5f64, 1000f64), (10f64, 20f64));
+
+        let index = index_of(&[("a", Bounds::new(0f64, 0f64, 50f64, 50f64))]);
+        assert_eq!(index.find_free_spot(0f64, 0f64, 50f64, 50f64, 10f64, 1000f64), (60f64, 0f64));
+        // no room right of the cell before max_x: the next row that clears it
+        let (x, y) = index.find_free_spot(0f64, 0f64, 50f64, 50f64, 10f64, 100f64);
+        assert_eq!(x, 0f64);
+        assert!(index.overlapping("", &Bounds::new(x, y, 50f64, 50f64), 10f64).is_none());
+    }
+
+    #[test]
+    fn find_free_spot_in_stays_inside_the_area() {
+        let area = Bounds::new(100f64, 100f64, 120f64, 60f64);
+        let index = index_of(&[("a", Bounds::new(100f64, 100f64, 50f64, 50f64))]);
+        assert_eq!(index.find_free_spot_in(&area, 50f64, 50f64, 10f64), Some((160f64, 100f64)));
+        let full = index_of(&[
+            ("a", Bounds::new(100f64, 100f64, 50f64, 50f64)),
+            ("b", Bounds::new(160f64, 100f64, 50f64, 50f64)),
+        ]);
+        // the next row would end below the area
+        assert_eq!(full.find_free_spot_in(&area, 50f64, 50f64, 10f64), None);
+        assert_eq!(index.find_free_spot_in(&area, 200f64, 10f64, 0f64), None);
+    }
+
+    #[test]
+    fn find_free_spot_near_prefers_the_closest_free_position() {
+        let index = index_of(&[
+            ("a", Bounds::new(100f64, 100f64, 50f64, 50f64)),
+            ("b", Bounds::new(200f64, 100f64, 50f64, 50f64)),
+        ]);
+        let wanted = Bounds::new(100f64, 100f64, 50f64, 50f64);
+        let (x, y) = index.find_free_spot_near("a", &wanted, 0f64);
+        assert!(index.overlapping("a", &Bounds::new(x, y, 50f64, 50f64), 0f64).is_none());
+        assert!(x >= 0f64 && y >= 0f64);
+        // with nothing around, one step away: half the larger side
+        let (x, y) = SpatialIndex::default().find_free_spot_near("", &wanted, 0f64);
+        assert_eq!((x - 100f64).hypot(y - 100f64), 25f64);
+    }
+}
@@ -0,0 +1,150 @@
+use gtk::{Align, CheckButton, Grid, Label, Popover, ToggleButton};
+use gtk::gio;
+use gtk::glib;
+use gtk::glib::clone;
+use gtk::glib::translate::{from_glib_full, ToGlibPtr};
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::prelude::{BoxExt, CheckButtonExt, FileExt, GridExt, ToggleButtonExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::files::DirItem;
+use crate::gtk_wrappers;
+use crate::gtk_wrappers::{alert, get_desktop};
+
+// shared with other tools through the freedesktop extended attribute, a comma separated list
+pub(crate) const TAGS_ATTRIBUTE: &str = "xattr::xdg.tags";
+// built-in tags and their colours; tags set by other tools are grey
+const TAGS: [(&str, &str); 5] = [
+    ("urgent", "#E01B24"),
+    ("waiting", "#FF7800"),
+    ("done", "#33D17A"),
+    ("review", "#3584E4"),
+    ("personal", "#9141AC"),
+];
+const OTHER_TAG_COLOR: &str = "#9A9996";
+const EMBLEM_SIZE: i32 = 16;
+
+fn tag_color(tag: &str) -> &'static str {
+    TAGS.iter().find(|(name, _)| *name == tag).map(|(_, color)| *color).unwrap_or(OTHER_TAG_COLOR)
+}
+
+pub(crate) fn parse_tags(value: &str) -> Vec<String> {
+    value.split(',').map(|t| t.trim()).filter(|t| !t.is_empty()).map(|t| t.to_string()).collect()
+}
+
+// writes the tags of a file to its extended attribute, removed with the last tag; shortcuts have no file of the folder to write to
+pub(crate) fn write_tags_attribute(path: &str, tags: &[String]) {
+    let file = gio::File::for_path(path);
+    let result = if tags.is_empty() {
+        remove_attribute(&file, TAGS_ATTRIBUTE)
+    } else {
+        file.set_attribute_string(TAGS_ATTRIBUTE, tags.join(",").as_str(), gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS, gio::Cancellable::NONE)
+    };
+    if let Err(err) = result {
+        println!("cannot write the tags of {}: {}", path, err);
+    }
+}
+
+// an attribute set to the invalid type is removed; gio-rs does not wrap g_file_set_attribute
+fn remove_attribute(file: &gio::File, attribute: &str) -> Result<(), glib::Error> {
+    unsafe {
+        let mut error = std::ptr::null_mut();
+        let done = gio::ffi::g_file_set_attribute(
+            file.to_glib_none().0,
+            attribute.to_glib_none().0,
+            gio::ffi::G_FILE_ATTRIBUTE_TYPE_INVALID,
+            std::ptr::null_mut(),
+            gio::ffi::G_FILE_QUERY_INFO_NOFOLLOW_SYMLINKS,
+            std::ptr::null_mut(),
+            &mut error,
+        );
+        if done == glib::ffi::GFALSE { Err(from_glib_full(error)) } else { Ok(()) }
+    }
+}
+
+// the row between the image and the label of a cell: emblems of the file, then a dot per tag; none when empty
+pub(crate) fn add_emblem_row(cell: &gtk::Box, dir_item: &DirItem, tags: &[String]) {
+    let emblems: Vec<(&str, &str)> = [
+        (dir_item.is_symlink, "emblem-symbolic-link", "symbolic link"),
+        (dir_item.read_only, "emblem-readonly", "read-only"),
+        (dir_item.unreadable, "emblem-unreadable", "cannot be read"),
+    ].into_iter().filter(|(shown, _, _)| *shown).map(|(_, icon, tooltip)| (icon, tooltip)).collect();
+    if emblems.is_empty() && tags.is_empty() {
+        return;
+    }
+    let row = gtk::Box::builder().orientation(Horizontal).spacing(2).halign(Align::Center).build();
+    for (icon_name, tooltip) in emblems {
+        let emblem = gtk::Image::from_icon_name(icon_name);
+        emblem.set_pixel_size(EMBLEM_SIZE);
+        emblem.set_tooltip_text(Some(tooltip));
+        row.append(&emblem);
+    }
+    for tag in tags {
+        let markup = format!("<span color=\"{}\">●</span>", tag_color(tag));
+        let dot = Label::builder().label(markup.as_str()).use_markup(true).tooltip_text(tag.as_str()).build();
+        row.append(&dot);
+    }
+    // the image stays the first child and the label the last one
+    cell.insert_child_after(&row, cell.first_child().as_ref());
+}
+
+// the tag section of the cell menu: toggles apply to all found icons when the icon is one of them
+pub(crate) fn make_tag_section(name: String, popover: &Popover) -> gtk::Box {
+    let section = gtk::Box::builder().orientation(Vertical).spacing(5).build();
+    section.append(&Label::new(Some("tags")));
+    let ds = gtk_wrappers::get_application(popover);
+    let mf = ds.imp().metafolder.borrow();
+    let tags = mf.tags_of(name.as_str());
+    let in_attributes = mf.xattr_tags;
+    drop(mf);
+    let mut all_tags: Vec<String> = TAGS.iter().map(|(tag, _)| tag.to_string()).collect();
+    all_tags.extend(tags.iter().filter(|t| !all_tags.contains(t)).cloned().collect::<Vec<String>>());
+
+    let grid = Grid::builder().row_spacing(2).column_spacing(2).halign(Align::Center).build();
+    for (i, tag) in all_tags.into_iter().enumerate() {
+        let markup = format!("<span color=\"{}\">●</span> {}", tag_color(tag.as_str()), glib::markup_escape_text(tag.as_str()));
+        let toggle = ToggleButton::builder().child(&Label::builder().label(markup.as_str()).use_markup(true).build()).active(tags.contains(&tag)).build();
+        toggle.connect_toggled(clone!(@strong name => move |t| {
+            let ds = gtk_wrappers::get_application(t);
+            let mut mf = ds.imp().metafolder.borrow_mut();
+            let names = mf.selection_for(name.as_str());
+            let result = mf.set_tag(&get_desktop(t), &names, tag.as_str(), t.is_active());
+            drop(mf);
+            if let Some(err) = result {
+                alert(t, "folder settings could not be saved".to_string(), err.to_string());
+            }
+        }));
+        grid.attach(&toggle, i as i32 % 3, i as i32 / 3, 1, 1);
+    }
+    section.append(&grid);
+
+    let attributes = CheckButton::builder().label("also in file attributes").active(in_attributes).build();
+    attributes.set_tooltip_text(Some("for all icons of this folder: tags are kept in user.xdg.tags too, where other tools see them"));
+    attributes.connect_toggled(|c| {
+        let ds = gtk_wrappers::get_application(c);
+        let result = ds.imp().metafolder.borrow_mut().set_xattr_tags(&get_desktop(c), c.is_active());
+        if let Some(err) = result {
+            alert(c, "folder settings could not be saved".to_string(), err.to_string());
+        }
+    });
+    section.append(&attributes);
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_tags, tag_color, OTHER_TAG_COLOR};
+
+    #[test]
+    fn parse_tags_trims_and_drops_empty_tags() {
+        assert_eq!(parse_tags("urgent, done ,,review"), vec!["urgent", "done", "review"]);
+        assert!(parse_tags("").is_empty());
+        assert!(parse_tags(" , ").is_empty());
+    }
+
+    #[test]
+    fn tags_of_other_tools_are_grey() {
+        assert_eq!(tag_color("urgent"), "#E01B24");
+        assert_eq!(tag_color("from-elsewhere"), OTHER_TAG_COLOR);
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, UNIX_EPOCH};
+
+use gtk::gdk_pixbuf::Pixbuf;
+use gtk::gio;
+use gtk::glib;
+use gtk::glib::ChecksumType;
+use gtk::gdk::Texture;
+use gtk::prelude::{CancellableExt, FileExt, ObjectExt};
+
+// freedesktop thumbnail cache: ~/.cache/thumbnails/<flavor>/<md5 of the file uri>.png, tagged with the uri and mtime of the file
+const NORMAL_SIZE: i32 = 128;
+const LARGE_SIZE: i32 = 256;
+// failed attempts are remembered per application, so that broken files are not retried on every load
+const FAIL_DIR: &str = "fail/metafolder";
+const THUMBNAILER_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Exec line of the installed thumbnailer of each mime type, read once
+static THUMBNAILERS: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+// shows the thumbnail of the file in the image once made and decoded off the main thread; until then the image keeps its icon
+pub(crate) fn load_thumbnail(image: &gtk::Image, file: String, mime_type: String, size: i32, cancellable: &gio::Cancellable) {
+    let image = image.downgrade();
+    let cancellable = cancellable.clone();
+    glib::spawn_future_local(async move {
+        let job_cancellable = cancellable.clone();
+        let texture = gio::spawn_blocking(move || {
+            if job_cancellable.is_cancelled() {
+                return None;
+            }
+            let thumbnail = thumbnail_for(file.as_str(), mime_type.as_str(), size)?;
+            Texture::from_filename(thumbnail).ok()
+        }).await;
+        if cancellable.is_cancelled() {
+            return;
+        }
+        if let (Some(image), Ok(Some(texture))) = (image.upgrade(), texture) {
+            image.set_paintable(Some(&texture));
+        }
+    });
+}
+
+// path of an up to date thumbnail of the file, generated if needed; None when no thumbnail can be made
+pub(crate) fn thumbnail_for(path: &str, mime_type: &str, size: i32) -> Option<String> {
+    let exec = thumbnailers().get(mime_type);
+    if !mime_type.starts_with("image/") && exec.is_none() {
+        return None;
+    }
+    let uri = gio::File::for_path(path).uri().to_string();
+    let mtime = fs::metadata(path).ok()?.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs().to_string();
+    let hash = glib::compute_checksum_for_data(ChecksumType::Md5, uri.as_bytes())?.to_string() + ".png";
+    let (flavor, flavor_size) = if size > NORMAL_SIZE { ("large", LARGE_SIZE) } else { ("normal", NORMAL_SIZE) };
+    let cache = glib::user_cache_dir().join("thumbnails");
+    let thumbnail = cache.join(flavor).join(hash.as_str());
+    if is_current(&thumbnail, mtime.as_str()) {
+        return thumbnail.to_str().map(|s| s.to_string());
+    }
+    let failed = cache.join(FAIL_DIR).join(hash.as_str());
+    if is_current(&failed, mtime.as_str()) {
+        return None;
+    }
+    let output = glib::tmp_dir().join(format!("metafolder-{}", hash));
+    match generate(path, mime_type, exec, uri.as_str(), &output, flavor_size) {
+        Some(pixbuf) => {
+            save(&pixbuf, &thumbnail, uri.as_str(), mtime.as_str())?;
+            thumbnail.to_str().map(|s| s.to_string())
+        }
+        None => {
+            // the failure marker is an empty image, as other applications do
+            if let Some(marker) = Pixbuf::new(gtk::gdk_pixbuf::Colorspace::Rgb, true, 8, 1, 1) {
+                save(&marker, &failed, uri.as_str(), mtime.as_str());
+            }
+            None
+        }
+    }
+}
+
+// a thumbnail is stale once the file has been modified after it was made
+fn is_current(thumbnail: &Path, mtime: &str) -> bool {
+    if !thumbnail.exists() {
+        return false;
+    }
+    match Pixbuf::from_file(thumbnail) {
+        Ok(pixbuf) => pixbuf.option("tEXt::Thumb::MTime").map(|m| m == mtime).unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+fn generate(path: &str, mime_type: &str, exec: Option<&String>, uri: &str, output: &Path, size: i32) -> Option<Pixbuf> {
+    if mime_type.starts_with("image/") {
+        if let Ok(pixbuf) = Pixbuf::from_file_at_scale(path, size, size, true) {
+            return Some(pixbuf);
+        }
+    }
+    let argv: Vec<String> = glib::shell_parse_argv(exec?).ok()?.iter()
+        .map(|arg| expand_arg(arg.to_string_lossy().as_ref(), path, uri, output.to_str().unwrap_or_default(), size))
+        .collect();
+    let mut process = subprocess::Popen::create(&argv, subprocess::PopenConfig::default()).ok()?;
+    match process.wait_timeout(THUMBNAILER_TIMEOUT) {
+        Ok(Some(status)) if status.success() => {}
+        Ok(None) => {
+            let _ = process.kill();
+            println!("thumbnailer timed out on {}", path);
+            return None;
+        }
+        _ => return None,
+    }
+    let pixbuf = Pixbuf::from_file_at_scale(output, size, size, true).ok();
+    let _ = fs::remove_file(output);
+    pixbuf
+}
+
+// the first installed thumbnailer of a mime type wins, user ones first
+fn thumbnailers() -> &'static HashMap<String, String> {
+    THUMBNAILERS.get_or_init(|| {
+        let mut thumbnailers = HashMap::new();
+        let dirs = std::iter::once(glib::user_data_dir()).chain(glib::system_data_dirs());
+        for dir in dirs {
+            let Ok(entries) = fs::read_dir(dir.join("thumbnailers")) else { continue };
+            for entry in entries.flatten() {
+                let key_file = glib::KeyFile::new();
+                if key_file.load_from_file(entry.path(), glib::KeyFileFlags::NONE).is_err() {
+                    continue;
+                }
+                let (Ok(mime_types), Ok(exec)) = (key_file.string_list("Thumbnailer Entry", "MimeType"), key_file.string("Thumbnailer Entry", "Exec")) else { continue };
+                for mime_type in mime_types.iter() {
+                    thumbnailers.entry(mime_type.to_string()).or_insert(exec.to_string());
+                }
+            }
+        }
+        thumbnailers
+    })
+}
+
+fn expand_arg(arg: &str, path: &str, uri: &str, output: &str, size: i32) -> String {
+    let mut expanded = String::new();
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => expanded.push_str(size.to_string().as_str()),
+            Some('u') => expanded.push_str(uri),
+            Some('i') => expanded.push_str(path),
+            Some('o') => expanded.push_str(output),
+            Some('%') => expanded.push('%'),
+            _ => {}
+        }
+    }
+    expanded
+}
+
+// written under a temporary name and renamed, so that readers never see a partial thumbnail
+fn save(pixbuf: &Pixbuf, thumbnail: &Path, uri: &str, mtime: &str) -> Option<()> {
+    fs::create_dir_all(thumbnail.parent()?).ok()?;
+    let temporary = thumbnail.with_extension(format!("png.{}", std::process::id()));
+    let options = [("tEXt::Thumb::URI", uri), ("tEXt::Thumb::MTime", mtime)];
+    if let Err(err) = pixbuf.savev(&temporary, "png", &options) {
+        println!("cannot save thumbnail {:?}: {}", thumbnail, err);
+        return None;
+    }
+    let _ = fs::set_permissions(&temporary, fs::Permissions::from_mode(0o600));
+    fs::rename(&temporary, thumbnail).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand_arg;
+
+    #[test]
+    fn expand_arg_fills_in_the_thumbnailer_placeholders() {
+        let expand = |arg| expand_arg(arg, "/a b/c.pdf", "file:///a%20b/c.pdf", "/tmp/out.png", 128);
+        assert_eq!(expand("-s %s"), "-s 128");
+        assert_eq!(expand("%u"), "file:///a%20b/c.pdf");
+        assert_eq!(expand("%i"), "/a b/c.pdf");
+        assert_eq!(expand("--output=%o"), "--output=/tmp/out.png");
+        assert_eq!(expand("100%%"), "100%");
+        // unknown and trailing placeholders are dropped
+        assert_eq!(expand("%x%"), "");
+    }
+}
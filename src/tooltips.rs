@@ -0,0 +1,243 @@
+use std::fs;
+use std::io::Read;
+
+use gtk::{Align, gio, glib, pango, Tooltip};
+use gtk::gdk::Texture;
+use gtk::Orientation::Vertical;
+use gtk::prelude::{BoxExt, FileExt, ObjectExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::files::DirItem;
+use crate::{files, gtk_wrappers, thumbnails};
+
+const PREVIEW_SIZE: i32 = 128;
+const PREVIEW_BYTES: u64 = 4096;
+const PREVIEW_LINES: usize = 8;
+const PREVIEW_COLUMNS: usize = 60;
+
+// read off the main thread the first time the tooltip of a cell shows
+#[derive(Debug)]
+pub(crate) struct TooltipDetails {
+    // the file the details were read from, stale once the cell is renamed or the folder changes
+    path: String,
+    description: String,
+    modified: Option<String>,
+    size: Option<u64>,
+    item_count: Option<usize>,
+    preview: Preview,
+}
+
+#[derive(Debug)]
+enum Preview {
+    None,
+    Image(Texture),
+    Text(String),
+}
+
+// shortcuts and symlinks show what they open
+fn summary(dir_item: &DirItem) -> String {
+    match (&dir_item.target, &dir_item.link_target) {
+        (Some(target), _) => target.clone(),
+        (None, Some(link_target)) if dir_item.broken_link => format!("{} → {} (broken link)", dir_item.name, link_target),
+        (None, Some(link_target)) => format!("{} → {}", dir_item.name, link_target),
+        (None, None) => dir_item.name.clone(),
+    }
+}
+
+// the summary shows at once; details and a preview replace it once read
+pub(crate) fn add_rich_tooltip(cell: &gtk::Box) {
+    cell.set_has_tooltip(true);
+    cell.connect_query_tooltip(|cell, _, _, _, tooltip| {
+        let ds = gtk_wrappers::get_application(cell);
+        let Ok(mf) = ds.imp().metafolder.try_borrow() else { return false };
+        let name = cell.widget_name().to_string();
+        let Some(dir_item) = mf.dir_items.get(&name) else { return false };
+        let summary = summary(dir_item);
+        let path = dir_item.target.clone().unwrap_or(mf.get_current_path() + name.as_str());
+        // URLs and broken links have nothing more to show
+        if path.contains("://") || dir_item.broken_link {
+            tooltip.set_text(Some(summary.as_str()));
+            return true;
+        }
+        let mut details = mf.tooltip_details.borrow_mut();
+        match details.get(&name) {
+            Some(Some(d)) if d.path == path => {
+                set_details(tooltip, summary.as_str(), d);
+                return true;
+            }
+            // being read
+            Some(None) => {}
+            _ => {
+                details.insert(name.clone(), None);
+                load_details(cell, name, path, dir_item.mime_type.clone(), dir_item.is_dir, mf.show_hidden);
+            }
+        }
+        tooltip.set_text(Some(summary.as_str()));
+        true
+    });
+}
+
+fn load_details(cell: &gtk::Box, name: String, path: String, mime_type: String, is_dir: bool, show_hidden: bool) {
+    let cell = cell.downgrade();
+    glib::spawn_future_local(async move {
+        let details = gio::spawn_blocking(move || read_details(path, mime_type.as_str(), is_dir, show_hidden)).await;
+        let Some(cell) = cell.upgrade() else { return };
+        let ds = gtk_wrappers::get_application(&cell);
+        // the entry is always replaced, else it would stay being read
+        let mf = ds.imp().metafolder.borrow();
+        match details {
+            Ok(details) => { mf.tooltip_details.borrow_mut().insert(name, Some(details)); }
+            // tried again on the next hover
+            Err(_) => { mf.tooltip_details.borrow_mut().remove(&name); }
+        }
+        drop(mf);
+        // shows the details if the tooltip is still open
+        cell.trigger_tooltip_query();
+    });
+}
+
+fn read_details(path: String, mime_type: &str, is_dir: bool, show_hidden: bool) -> TooltipDetails {
+    let info = gio::File::for_path(path.as_str()).query_info("standard::size,time::modified", gio::FileQueryInfoFlags::NONE, gio::Cancellable::NONE).ok();
+    let modified = info.as_ref()
+        .and_then(|i| i.modification_date_time())
+        .and_then(|m| m.to_local().ok())
+        .and_then(|m| m.format("%x %X").ok())
+        .map(|m| m.to_string());
+    let size = info.as_ref().filter(|_| !is_dir).map(|i| i.size() as u64);
+    let item_count = if is_dir { count_items(path.as_str(), show_hidden) } else { None };
+    let preview = if is_dir { Preview::None } else { read_preview(path.as_str(), mime_type) };
+    TooltipDetails {
+        description: gio::content_type_get_description(mime_type).to_string(),
+        path,
+        modified,
+        size,
+        item_count,
+        preview,
+    }
+}
+
+// the items the folder would show, with its own ignore rules
+fn count_items(path: &str, show_hidden: bool) -> Option<usize> {
+    let ignore_rules = files::load_ignore_rules((path.to_string() + "/").as_str());
+    let entries = gio::File::for_path(path).enumerate_children("standard::name,standard::type,standard::is-hidden", gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS, gio::Cancellable::NONE).ok()?;
+    Some(entries.filter_map(|info| info.ok())
+        .map(|info| DirItem {
+            name: info.name().to_string_lossy().to_string(),
+            is_hidden: info.is_hidden(),
+            is_dir: info.file_type() == gio::FileType::Directory,
+            ..Default::default()
+        })
+        .filter(|dir_item| files::is_shown(dir_item, show_hidden, &ignore_rules))
+        .count())
+}
+
+// a thumbnail when one can be made, else the first lines of text files
+fn read_preview(path: &str, mime_type: &str) -> Preview {
+    if let Some(texture) = thumbnails::thumbnail_for(path, mime_type, PREVIEW_SIZE).and_then(|t| Texture::from_filename(t).ok()) {
+        return Preview::Image(texture);
+    }
+    if !gio::content_type_is_a(mime_type, "text/plain") {
+        return Preview::None;
+    }
+    let mut content = Vec::new();
+    let Ok(file) = fs::File::open(path) else { return Preview::None };
+    if file.take(PREVIEW_BYTES).read_to_end(&mut content).is_err() || content.contains(&0) {
+        return Preview::None;
+    }
+    let text = String::from_utf8_lossy(content.as_slice()).lines()
+        .take(PREVIEW_LINES)
+        .map(|line| line.chars().take(PREVIEW_COLUMNS).collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n");
+    if text.trim().is_empty() { Preview::None } else { Preview::Text(text) }
+}
+
+fn set_details(tooltip: &Tooltip, summary: &str, details: &TooltipDetails) {
+    let container = gtk::Box::builder().orientation(Vertical).spacing(5).build();
+    let mut lines = vec![format!("<b>{}</b>", glib::markup_escape_text(summary)), glib::markup_escape_text(details.description.as_str()).to_string()];
+    if let Some(size) = details.size {
+        lines.push(glib::format_size(size).to_string());
+    }
+    if let Some(count) = details.item_count {
+        lines.push(if count == 1 { "1 item".to_string() } else { format!("{} items", count) });
+    }
+    if let Some(modified) = &details.modified {
+        lines.push(format!("modified {}", glib::markup_escape_text(modified.as_str())));
+    }
+    let label = gtk::Label::builder().label(lines.join("\n").as_str()).use_markup(true).halign(Align::Start).build();
+    container.append(&label);
+    match &details.preview {
+        Preview::Image(texture) => {
+            let picture = gtk::Picture::for_paintable(texture);
+            picture.set_can_shrink(false);
+            picture.set_halign(Align::Center);
+            container.append(&picture);
+        }
+        Preview::Text(text) => {
+            let preview = gtk::Label::builder().label(text.as_str()).halign(Align::Start).ellipsize(pango::EllipsizeMode::End).build();
+            preview.add_css_class("monospace");
+            container.append(&preview);
+        }
+        Preview::None => {}
+    }
+    tooltip.set_custom(Some(&container));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::files::DirItem;
+
+    use super::{count_items, Preview, read_preview, summary};
+
+    fn temp_folder(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("metafolder-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path.to_str().unwrap().to_string() + "/"
+    }
+
+    #[test]
+    fn summary_shows_what_shortcuts_and_links_open() {
+        let item = DirItem { name: "notes".to_string(), ..Default::default() };
+        assert_eq!(summary(&item), "notes");
+        let shortcut = DirItem { target: Some("https://example.org".to_string()), ..item.clone() };
+        assert_eq!(summary(&shortcut), "https://example.org");
+        let link = DirItem { link_target: Some("/tmp/notes.txt".to_string()), ..item.clone() };
+        assert_eq!(summary(&link), "notes → /tmp/notes.txt");
+        let broken = DirItem { broken_link: true, ..link };
+        assert_eq!(summary(&broken), "notes → /tmp/notes.txt (broken link)");
+    }
+
+    #[test]
+    fn count_items_counts_what_the_folder_would_show() {
+        let folder = temp_folder("count-items");
+        fs::write(folder.clone() + "notes.txt", "").unwrap();
+        fs::write(folder.clone() + ".profile", "").unwrap();
+        fs::write(folder.clone() + "draft.tmp", "").unwrap();
+        fs::create_dir(folder.clone() + "photos").unwrap();
+        // temporary files are ignored by default, hidden ones only count when shown
+        assert_eq!(count_items(folder.as_str(), false), Some(2));
+        assert_eq!(count_items(folder.as_str(), true), Some(3));
+        assert_eq!(count_items((folder.clone() + "missing").as_str(), false), None);
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn read_preview_shows_the_start_of_text_files() {
+        let folder = temp_folder("read-preview");
+        let long_line = "x".repeat(100);
+        fs::write(folder.clone() + "long.txt", vec![long_line.as_str(); 20].join("\n")).unwrap();
+        let Preview::Text(text) = read_preview((folder.clone() + "long.txt").as_str(), "text/plain") else { panic!("no text preview") };
+        assert_eq!(text.lines().count(), 8);
+        assert!(text.lines().all(|line| line.chars().count() == 60));
+        // binary content, blank text and other types have no preview
+        fs::write(folder.clone() + "data.txt", b"text\0more").unwrap();
+        assert!(matches!(read_preview((folder.clone() + "data.txt").as_str(), "text/plain"), Preview::None));
+        fs::write(folder.clone() + "blank.txt", "  \n\n").unwrap();
+        assert!(matches!(read_preview((folder.clone() + "blank.txt").as_str(), "text/plain"), Preview::None));
+        assert!(matches!(read_preview((folder.clone() + "long.txt").as_str(), "application/octet-stream"), Preview::None));
+        fs::remove_dir_all(folder).unwrap();
+    }
+}
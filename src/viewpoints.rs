@@ -0,0 +1,135 @@
+use gtk::{ApplicationWindow, Button, Entry, EventControllerKey, Label, ListBox, Popover};
+use gtk::gdk::ModifierType;
+use gtk::glib;
+use gtk::glib::{clone, Propagation};
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::prelude::{AdjustmentExt, BoxExt, ButtonExt, EditableExt, EventControllerExt, IsA, PopoverExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::files::MemoViewpoint;
+use crate::{gtk_wrappers, zoom};
+use crate::gtk_wrappers::{alert, get_desktop, get_scrolled_window, set_visual_zoom_switch, set_zoom_widgets};
+
+// named viewpoints of the folder; ctrl+1 to ctrl+9 go to the first nine
+pub(crate) fn make_viewpoints() -> Popover {
+    let container = gtk::Box::builder().orientation(Vertical).spacing(10).build();
+    let popover = Popover::builder().child(&container).build();
+
+    let list = ListBox::builder().build();
+    list.set_placeholder(Some(&Label::new(Some("no viewpoints"))));
+    container.append(&list);
+
+    let save_box = gtk::Box::builder().orientation(Horizontal).spacing(5).build();
+    let name_entry = Entry::builder().placeholder_text("viewpoint name").build();
+    save_box.append(&name_entry);
+    let save = Button::builder().label("save current view").build();
+    save.connect_clicked(clone!(@weak name_entry, @weak list, @weak popover => move |b| {
+        let name = name_entry.text().trim().to_string();
+        if name.is_empty() {
+            return;
+        }
+        let viewpoint = current_viewpoint(b, name);
+        let ds = gtk_wrappers::get_application(b);
+        let result = ds.imp().metafolder.borrow_mut().save_viewpoint(viewpoint);
+        if let Some(err) = result {
+            alert(b, "folder settings could not be saved".to_string(), err.to_string());
+        }
+        name_entry.set_text("");
+        fill_list(&list, &popover);
+    }));
+    save_box.append(&save);
+    container.append(&save_box);
+
+    // the viewpoints belong to the folder shown, which may have changed since the last time
+    popover.connect_show(clone!(@weak list => move |p| {
+        fill_list(&list, p);
+    }));
+    popover
+}
+
+fn fill_list(list: &ListBox, popover: &Popover) {
+    list.remove_all();
+    let ds = gtk_wrappers::get_application(list);
+    let viewpoints = ds.imp().metafolder.borrow().viewpoints.clone();
+    for (index, viewpoint) in viewpoints.into_iter().enumerate() {
+        let row = gtk::Box::builder().orientation(Horizontal).spacing(5).build();
+        let go = Button::builder().label(viewpoint.name.as_str()).hexpand(true).build();
+        if index < 9 {
+            go.set_tooltip_text(Some(format!("ctrl+{}", index + 1).as_str()));
+        }
+        go.connect_clicked(clone!(@weak popover => move |b| {
+            go_to_viewpoint(b, index);
+            popover.popdown();
+        }));
+        row.append(&go);
+        let delete = Button::from_icon_name("edit-delete-symbolic");
+        delete.set_tooltip_text(Some("forget this viewpoint"));
+        delete.connect_clicked(clone!(@weak list, @weak popover => move |b| {
+            let ds = gtk_wrappers::get_application(b);
+            let result = ds.imp().metafolder.borrow_mut().delete_viewpoint(viewpoint.name.as_str());
+            if let Some(err) = result {
+                alert(b, "folder settings could not be saved".to_string(), err.to_string());
+            }
+            fill_list(&list, &popover);
+        }));
+        row.append(&delete);
+        list.append(&row);
+    }
+}
+
+fn current_viewpoint(w: &impl IsA<gtk::Widget>, name: String) -> MemoViewpoint {
+    let scrolled_window = get_scrolled_window(w);
+    let ds = gtk_wrappers::get_application(w);
+    let mf = ds.imp().metafolder.borrow();
+    MemoViewpoint {
+        name,
+        scroll_x: scrolled_window.hadjustment().value() as i32,
+        scroll_y: scrolled_window.vadjustment().value() as i32,
+        zoom: mf.zoom,
+        zoom_x: mf.zoom_x,
+        zoom_y: mf.zoom_y,
+        view_scale: mf.view_scale,
+    }
+}
+
+// restores zoom, visual zoom and scroll offset; the zoom is not saved, as when auto fitting
+pub(crate) fn go_to_viewpoint(w: &impl IsA<gtk::Widget>, index: usize) {
+    let ds = gtk_wrappers::get_application(w);
+    let Some(viewpoint) = ds.imp().metafolder.borrow().viewpoints.get(index).cloned() else { return };
+    if viewpoint.zoom {
+        ds.imp().metafolder.borrow_mut().zoom(viewpoint.zoom_x, viewpoint.zoom_y, w);
+        set_zoom_widgets(w, true, viewpoint.zoom_x, viewpoint.zoom_y);
+    } else {
+        ds.imp().metafolder.borrow_mut().unzoom(w);
+    }
+    let view_scale = viewpoint.view_scale.clamp(zoom::MIN_VIEW_SCALE, zoom::MAX_VIEW_SCALE);
+    // a scaled viewpoint turns visual zoom on, so that the scale can be changed and reset again
+    if view_scale != 1f64 {
+        set_visual_zoom_switch(w, true);
+    }
+    ds.imp().metafolder.borrow_mut().set_view_scale(view_scale, &get_desktop(w));
+    let scrolled_window = get_scrolled_window(w);
+    let h = scrolled_window.hadjustment();
+    let v = scrolled_window.vadjustment();
+    // the adjustments only grow once the desktop has been measured again
+    glib::idle_add_local_once(clone!(@weak h, @weak v => move || {
+        h.set_value(viewpoint.scroll_x as f64);
+        v.set_value(viewpoint.scroll_y as f64);
+    }));
+}
+
+pub(crate) fn add_viewpoint_shortcuts(window: &ApplicationWindow) {
+    let keys = EventControllerKey::new();
+    keys.connect_key_pressed(|controller, key, _, modifiers| {
+        if !modifiers.contains(ModifierType::CONTROL_MASK) {
+            return Propagation::Proceed;
+        }
+        let Some(digit) = key.to_unicode().and_then(|c| c.to_digit(10)) else { return Propagation::Proceed };
+        if digit == 0 {
+            return Propagation::Proceed;
+        }
+        go_to_viewpoint(&controller.widget(), digit as usize - 1);
+        Propagation::Stop
+    });
+    window.add_controller(keys);
+}
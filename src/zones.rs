@@ -0,0 +1,274 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::{Align, Button, ColorDialog, ColorDialogButton, DrawingArea, Entry, GestureClick, GestureDrag, Label, Overlay, Popover};
+use gtk::gdk;
+use gtk::gdk::RGBA;
+use gtk::glib;
+use gtk::glib::clone;
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::prelude::{BoxExt, ButtonExt, Cast, DrawingAreaExtManual, EditableExt, GestureDragExt, GestureExt, GestureSingleExt, IsA, PopoverExt, WidgetExt};
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::files::MemoZone;
+use crate::gtk_wrappers;
+use crate::gtk_wrappers::{alert, drag_point_on_desktop, get_desktop, get_visible_area};
+
+// room left for the title when placing new files in a zone
+pub(crate) const ZONE_TITLE_HEIGHT: f64 = 30f64;
+const DEFAULT_ZONE_COLOR: &str = "rgba(100, 149, 237, 0.25)";
+const DEFAULT_ZONE_WIDTH: i32 = 300;
+const DEFAULT_ZONE_HEIGHT: i32 = 200;
+const MIN_ZONE_SIZE: f64 = 50f64;
+
+// pointer, zone origin and cell positions at the start of a drag, in view coordinates
+type DragStart = ((f64, f64), (f64, f64), Vec<(String, f64, f64)>);
+
+pub(crate) fn make_zone_widget(zone: &MemoZone) -> Overlay {
+    let area = DrawingArea::new();
+    let widget = Overlay::builder().child(&area).build();
+
+    let title = Label::builder().halign(Align::Start).valign(Align::Start).margin_start(8).margin_top(5).use_markup(true).build();
+    widget.add_overlay(&title);
+    let handle = gtk::Image::from_icon_name("list-drag-handle-symbolic");
+    handle.set_halign(Align::End);
+    handle.set_valign(Align::End);
+    handle.set_tooltip_text(Some("drag to resize"));
+    handle.set_cursor_from_name(Some("se-resize"));
+    handle.add_controller(make_resize_gesture(&widget));
+    widget.add_overlay(&handle);
+
+    widget.add_controller(make_move_gesture(&widget));
+    let menu_gesture = GestureClick::new();
+    menu_gesture.set_button(gdk::BUTTON_SECONDARY);
+    menu_gesture.connect_pressed(clone!(@weak widget => move |g, _, _, _| {
+        g.set_state(gtk::EventSequenceState::Claimed);
+        show_zone_menu(&widget);
+    }));
+    widget.add_controller(menu_gesture);
+
+    style_zone(&widget, zone);
+    widget
+}
+
+// title and colour of a zone widget
+fn style_zone(widget: &Overlay, zone: &MemoZone) {
+    let rgba = RGBA::parse(zone.color.as_str()).unwrap_or(RGBA::parse(DEFAULT_ZONE_COLOR).unwrap());
+    let area = widget.child().unwrap().downcast::<DrawingArea>().unwrap();
+    area.set_draw_func(move |_, cr, width, height| {
+        cr.set_source_rgba(rgba.red() as f64, rgba.green() as f64, rgba.blue() as f64, rgba.alpha() as f64);
+        cr.rectangle(0f64, 0f64, width as f64, height as f64);
+        let _ = cr.fill_preserve();
+        cr.set_source_rgba(rgba.red() as f64, rgba.green() as f64, rgba.blue() as f64, 1f64);
+        cr.set_line_width(2f64);
+        let _ = cr.stroke();
+    });
+    area.queue_draw();
+    let title = area.next_sibling().unwrap().downcast::<Label>().unwrap();
+    let markup = String::from("<span font_weight=\"bold\">") + glib::markup_escape_text(zone.title.as_str()).as_str() + "</span>";
+    title.set_label(markup.as_str());
+}
+
+// zones and annotations move under the same conditions as cells
+pub(crate) fn can_move(w: &impl IsA<gtk::Widget>) -> bool {
+    let ds = gtk_wrappers::get_application(w);
+    let mf = ds.imp().metafolder.borrow();
+    if mf.locked {
+        return false;
+    }
+    if mf.zoom {
+        alert(w, "Cannot move".to_string(), "Desktop is zoomed - either unzoom or commit to move".to_string());
+        return false;
+    }
+    true
+}
+
+// dragging a zone moves the cells inside it along
+fn make_move_gesture(widget: &Overlay) -> GestureDrag {
+    let start: Rc<RefCell<DragStart>> = Rc::new(RefCell::new(((0f64, 0f64), (0f64, 0f64), Vec::new())));
+    let gesture = GestureDrag::new();
+    gesture.connect_drag_begin(clone!(@strong start, @weak widget => move |g, _, _| {
+        if !can_move(&widget) {
+            g.set_state(gtk::EventSequenceState::Denied);
+            return;
+        }
+        g.set_state(gtk::EventSequenceState::Claimed);
+        let ds = gtk_wrappers::get_application(&widget);
+        let mf = ds.imp().metafolder.borrow();
+        let Some(index) = mf.zone_index(&widget) else { return };
+        let zone_bounds = mf.zone_view_bounds(&mf.zones[index]);
+        let cells = mf.cells_in_zone(index).into_iter()
+            .filter_map(|name| {
+                let b = mf.spatial_index.borrow().get(name.as_str())?;
+                Some((name, b.x, b.y))
+            })
+            .collect();
+        start.replace((drag_point_on_desktop(g, 0f64, 0f64), (zone_bounds.x, zone_bounds.y), cells));
+    }));
+    gesture.connect_drag_update(clone!(@strong start, @weak widget => move |g, offset_x, offset_y| {
+        let ds = gtk_wrappers::get_application(&widget);
+        let mut mf = ds.imp().metafolder.borrow_mut();
+        let Some(index) = mf.zone_index(&widget) else { return };
+        let desktop = get_desktop(&widget);
+        let ((pointer_x, pointer_y), (zone_x, zone_y), cells) = &*start.borrow();
+        let (x, y) = drag_point_on_desktop(g, offset_x, offset_y);
+        let (offset_x, offset_y) = (x - pointer_x, y - pointer_y);
+        let (model_x, model_y) = mf.view_to_model((zone_x + offset_x).max(0f64), (zone_y + offset_y).max(0f64));
+        mf.zones[index].x = model_x.round() as i32;
+        mf.zones[index].y = model_y.round() as i32;
+        mf.show_zones(&desktop);
+        for (name, x, y) in cells {
+            mf.move_cell(&desktop, name.as_str(), (x + offset_x).max(0f64), (y + offset_y).max(0f64));
+        }
+    }));
+    gesture.connect_drag_end(clone!(@weak widget => move |_, _, _| {
+        let ds = gtk_wrappers::get_application(&widget);
+        let mut mf = ds.imp().metafolder.borrow_mut();
+        let mut result = mf.save_zones();
+        if result.is_none() {
+            result = mf.scan_positions_and_save_settings("", 0f64, 0f64);
+        }
+        drop(mf);
+        if let Some(err) = result {
+            alert(&widget, "folder settings could not be saved".to_string(), err.to_string());
+        }
+    }));
+    gesture
+}
+
+fn make_resize_gesture(widget: &Overlay) -> GestureDrag {
+    // pointer and zone size at the start of the drag
+    let start_size = Rc::new(RefCell::new(((0f64, 0f64), (0f64, 0f64))));
+    let gesture = GestureDrag::new();
+    gesture.connect_drag_begin(clone!(@strong start_size, @weak widget => move |g, _, _| {
+        if !can_move(&widget) {
+            g.set_state(gtk::EventSequenceState::Denied);
+            return;
+        }
+        g.set_state(gtk::EventSequenceState::Claimed);
+        start_size.replace((drag_point_on_desktop(g, 0f64, 0f64), (widget.width() as f64, widget.height() as f64)));
+    }));
+    gesture.connect_drag_update(clone!(@strong start_size, @weak widget => move |g, offset_x, offset_y| {
+        let ds = gtk_wrappers::get_application(&widget);
+        let mut mf = ds.imp().metafolder.borrow_mut();
+        let Some(index) = mf.zone_index(&widget) else { return };
+        let ((pointer_x, pointer_y), (width, height)) = *start_size.borrow();
+        let (x, y) = drag_point_on_desktop(g, offset_x, offset_y);
+        let (offset_x, offset_y) = (x - pointer_x, y - pointer_y);
+        let b = mf.zone_view_bounds(&mf.zones[index]);
+        let (right, bottom) = mf.view_to_model(b.x + (width + offset_x).max(MIN_ZONE_SIZE), b.y + (height + offset_y).max(MIN_ZONE_SIZE));
+        let zone = &mut mf.zones[index];
+        zone.width = right.round() as i32 - zone.x;
+        zone.height = bottom.round() as i32 - zone.y;
+        mf.show_zones(&get_desktop(&widget));
+    }));
+    gesture.connect_drag_end(clone!(@weak widget => move |_, _, _| {
+        let ds = gtk_wrappers::get_application(&widget);
+        let result = ds.imp().metafolder.borrow_mut().save_zones();
+        if let Some(err) = result {
+            alert(&widget, "folder settings could not be saved".to_string(), err.to_string());
+        }
+    }));
+    gesture
+}
+
+// adds a zone in the middle of the part of the desktop in view, and opens its menu
+pub(crate) fn add_zone(w: &impl IsA<gtk::Widget>) {
+    let visible = get_visible_area(w);
+    let desktop = get_desktop(w);
+    let ds = gtk_wrappers::get_application(w);
+    let mut mf = ds.imp().metafolder.borrow_mut();
+    let (x, y) = mf.view_to_model((visible.x + (visible.w - DEFAULT_ZONE_WIDTH as f64) / 2f64).max(0f64),
+                                  (visible.y + (visible.h - DEFAULT_ZONE_HEIGHT as f64) / 2f64).max(0f64));
+    let zone = MemoZone {
+        title: "zone".to_string(),
+        color: DEFAULT_ZONE_COLOR.to_string(),
+        x: x.round() as i32,
+        y: y.round() as i32,
+        width: DEFAULT_ZONE_WIDTH,
+        height: DEFAULT_ZONE_HEIGHT,
+        accepts: "".to_string(),
+    };
+    let widget = make_zone_widget(&zone);
+    mf.add_zone(&desktop, zone, widget.clone());
+    let result = mf.save_zones();
+    drop(mf);
+    if let Some(err) = result {
+        alert(w, "folder settings could not be saved".to_string(), err.to_string());
+    }
+    show_zone_menu(&widget);
+}
+
+fn show_zone_menu(widget: &Overlay) {
+    let ds = gtk_wrappers::get_application(widget);
+    let mf = ds.imp().metafolder.borrow();
+    let Some(index) = mf.zone_index(widget) else { return };
+    let zone = mf.zones[index].clone();
+    drop(mf);
+
+    let container = gtk::Box::builder().orientation(Vertical).spacing(10).build();
+    let popover = Popover::builder().child(&container).build();
+
+    let title_entry = Entry::builder().text(zone.title.as_str()).placeholder_text("title").build();
+    container.append(&title_entry);
+    let color_button = ColorDialogButton::builder().dialog(&ColorDialog::builder().modal(true).title("Pick a zone color").with_alpha(true).build()).build();
+    color_button.set_rgba(&RGBA::parse(zone.color.as_str()).unwrap_or(RGBA::parse(DEFAULT_ZONE_COLOR).unwrap()));
+    container.append(&color_button);
+    let accepts_entry = Entry::builder().text(zone.accepts.as_str()).placeholder_text("new files here, e.g. image/, .pdf").build();
+    accepts_entry.set_tooltip_text(Some("mime type prefixes or extensions of new files placed in this zone, when placing new files into matching zones"));
+    container.append(&accepts_entry);
+
+    let buttons = gtk::Box::builder().orientation(Horizontal).spacing(5).build();
+    let apply = Button::builder().label("apply").build();
+    apply.connect_clicked(clone!(@weak widget, @weak popover, @weak title_entry, @weak color_button, @weak accepts_entry => move |_| {
+        let ds = gtk_wrappers::get_application(&widget);
+        let mut mf = ds.imp().metafolder.borrow_mut();
+        let Some(index) = mf.zone_index(&widget) else { return };
+        let zone = &mut mf.zones[index];
+        zone.title = title_entry.text().to_string();
+        zone.color = color_button.rgba().to_string();
+        zone.accepts = accepts_entry.text().to_string();
+        style_zone(&widget, zone);
+        let result = mf.save_zones();
+        drop(mf);
+        if let Some(err) = result {
+            alert(&widget, "folder settings could not be saved".to_string(), err.to_string());
+        }
+        popover.popdown();
+    }));
+    buttons.append(&apply);
+    let delete = Button::builder().label("delete zone").build();
+    delete.set_tooltip_text(Some("the icons inside stay where they are"));
+    delete.connect_clicked(clone!(@weak widget, @weak popover => move |_| {
+        let ds = gtk_wrappers::get_application(&widget);
+        let desktop = get_desktop(&widget);
+        let mut mf = ds.imp().metafolder.borrow_mut();
+        let Some(index) = mf.zone_index(&widget) else { return };
+        popover.popdown();
+        let result = mf.delete_zone(&desktop, index);
+        drop(mf);
+        if let Some(err) = result {
+            alert(&desktop, "folder settings could not be saved".to_string(), err.to_string());
+        }
+    }));
+    buttons.append(&delete);
+    container.append(&buttons);
+
+    // parented to the desktop, as the menus of cells
+    let desktop = get_desktop(widget);
+    popover.set_parent(&desktop);
+    let b = widget.compute_bounds(&desktop).expect("Fatal: cannot get zone bounds");
+    popover.set_pointing_to(Some(&gdk::Rectangle::new(b.x() as i32, b.y() as i32, b.width() as i32, ZONE_TITLE_HEIGHT as i32)));
+    popover.connect_closed(|p| {
+        glib::idle_add_local_once(clone!(@weak p => move || p.unparent()));
+    });
+    popover.popup();
+}
+
+pub(crate) fn draw_zones(desktop: &gtk::Fixed, zones: &[MemoZone]) {
+    let ds = gtk_wrappers::get_application(desktop);
+    let mut mf = ds.imp().metafolder.borrow_mut();
+    for zone in zones {
+        mf.add_zone(desktop, zone.clone(), make_zone_widget(zone));
+    }
+}
@@ -1,16 +1,29 @@
-use gtk::{GestureClick, Popover, PositionType, Scale};
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gtk::{EventControllerMotion, EventControllerScroll, EventControllerScrollFlags, GestureClick, GestureZoom, Popover, PositionType, PropagationPhase, Scale, ScrolledWindow, Switch};
+use gtk::gdk::ModifierType;
 use gtk::glib;
+use gtk::glib::Propagation;
 use gtk::prelude::*;
 use gtk::subclass::prelude::ObjectSubclassIsExt;
 
 use crate::glib::clone;
 use crate::gtk_wrappers;
+use crate::gtk_wrappers::{alert, get_desktop, is_visual_zoom_enabled};
+
+pub(crate) const MIN_VIEW_SCALE: f64 = 0.25;
+pub(crate) const MAX_VIEW_SCALE: f64 = 4.0;
+const VIEW_SCALE_STEP: f64 = 1.1;
+// must coincide with the bounds of the zoom scales
+const MIN_ZOOM: f64 = 50.0;
+const MAX_ZOOM: f64 = 200.0;
 
-pub(crate) fn make_zoom() -> (Popover, Scale, Scale) {
+pub(crate) fn make_zoom() -> (Popover, Scale, Scale, gtk::Button, Switch, Switch) {
     let horizontal_adjustment = gtk::Adjustment::new(
         100.0,   // The value where the handle will be at the initial state
-        50.0,   // Lower bound
-        200.0, // Upper bound
+        MIN_ZOOM,   // Lower bound
+        MAX_ZOOM, // Upper bound
         10.0,   // Step increment, keep it 0 if you don't want it to be operated by arrow keys
         20.0,   // Page increment
         0.0,   // Page size
@@ -23,8 +36,8 @@ pub(crate) fn make_zoom() -> (Popover, Scale, Scale) {
 
     let vertical_adjustment = gtk::Adjustment::new(
         100.0,   // The value where the handle will be at the initial state
-        50.0,   // Lower bound
-        200.0, // Upper bound
+        MIN_ZOOM,   // Lower bound
+        MAX_ZOOM, // Upper bound
         10.0,   // Step increment, keep it 0 if you don't want it to be operated by arrow keys
         20.0,   // Page increment
         0.0,   // Page size
@@ -49,7 +62,10 @@ pub(crate) fn make_zoom() -> (Popover, Scale, Scale) {
     let commit = gtk::Button::builder().label("commit").build();
     commit.connect_clicked(clone!(@weak popover, @weak zoomx, @weak zoomy =>  move|_b| {
         let ds = gtk_wrappers::get_application(&popover);
-        ds.imp().metafolder.borrow_mut().zoom_commit_and_save_settings(&popover);
+        let result = ds.imp().metafolder.borrow_mut().zoom_commit_and_save_settings(&popover);
+        if let Some(err) = result {
+            alert(&popover, "folder settings could not be saved".to_string(), err.to_string());
+        }
     }));
     grid.put(&commit, 0f64, 0f64);
 
@@ -60,6 +76,43 @@ pub(crate) fn make_zoom() -> (Popover, Scale, Scale) {
     }));
     grid.put(&zero, 100f64, 0f64);
 
+    let visual_zoom_container = gtk::Box::builder().orientation(gtk::Orientation::Horizontal).build();
+    visual_zoom_container.append(&gtk::Label::builder().label("scale icons    ").build());
+    let visual_zoom_switch = Switch::builder().state(false).active(false).build();
+    visual_zoom_switch.set_tooltip_text(Some("ctrl+scroll or pinch scales the whole folder, icons and labels included - view only, never saved"));
+    visual_zoom_switch.connect_active_notify(clone!(@weak popover => move |sw| {
+        if !sw.is_active() {
+            let ds = gtk_wrappers::get_application(&popover);
+            ds.imp().metafolder.borrow_mut().set_view_scale(1f64, &get_desktop(&popover));
+        }
+    }));
+    visual_zoom_container.append(&visual_zoom_switch);
+    grid.put(&visual_zoom_container, 0f64, 60f64);
+
+    let fit = gtk::Button::builder().label("fit").build();
+    fit.set_tooltip_text(Some("zoom so that all icons fit in the window"));
+    fit.connect_clicked(clone!(@weak popover => move |_b| {
+        let ds = gtk_wrappers::get_application(&popover);
+        let result = ds.imp().metafolder.borrow_mut().fit_and_save_settings(&popover);
+        if let Some(err) = result {
+            alert(&popover, "folder settings could not be saved".to_string(), err.to_string());
+        }
+    }));
+    grid.put(&fit, 170f64, 0f64);
+
+    let auto_fit_container = gtk::Box::builder().orientation(gtk::Orientation::Horizontal).build();
+    auto_fit_container.append(&gtk::Label::builder().label("auto fit        ").build());
+    let auto_fit_switch = Switch::builder().state(false).active(false).build();
+    auto_fit_switch.set_tooltip_text(Some("fit all icons in the window whenever it is resized - not saved"));
+    auto_fit_switch.connect_active_notify(clone!(@weak popover => move |sw| {
+        if sw.is_active() {
+            let ds = gtk_wrappers::get_application(&popover);
+            ds.imp().metafolder.borrow_mut().fit_to_window(&popover);
+        }
+    }));
+    auto_fit_container.append(&auto_fit_switch);
+    grid.put(&auto_fit_container, 0f64, 110f64);
+
     let gesture_click_y = GestureClick::new();
     gesture_click_y.connect_unpaired_release(clone!(@weak zoomx, @weak zoomy, @weak popover => move |_click, _, _, _, _|{
         let ds = gtk_wrappers::get_application(&popover);
@@ -82,6 +135,154 @@ pub(crate) fn make_zoom() -> (Popover, Scale, Scale) {
     }));
     zoomx.add_controller(gesture_click_x);
 
-    (popover, zoomx, zoomy)
+    (popover, zoomx, zoomy, commit, visual_zoom_switch, auto_fit_switch)
+}
+
+
+// ctrl+scroll and touchpad pinch scale the whole desktop around the pointer when visual zoom is enabled
+pub(crate) fn add_visual_zoom_controllers(scrolled_window: &ScrolledWindow) {
+    let pointer = Rc::new(Cell::new((0f64, 0f64)));
+    let motion = EventControllerMotion::new();
+    motion.connect_motion(clone!(@strong pointer => move |_, x, y| {
+        pointer.set((x, y));
+    }));
+    scrolled_window.add_controller(motion);
+
+    let scroll = EventControllerScroll::new(EventControllerScrollFlags::VERTICAL);
+    // capture phase, otherwise the scrolled window scrolls first
+    scroll.set_propagation_phase(PropagationPhase::Capture);
+    scroll.connect_scroll(clone!(@strong pointer, @weak scrolled_window => @default-return Propagation::Proceed, move |controller, _, dy| {
+        if !controller.current_event_state().contains(ModifierType::CONTROL_MASK) || !is_visual_zoom_enabled(&scrolled_window) {
+            return Propagation::Proceed;
+        }
+        let ds = gtk_wrappers::get_application(&scrolled_window);
+        let current = ds.imp().metafolder.borrow().view_scale;
+        let scale = if dy < 0f64 { current * VIEW_SCALE_STEP } else { current / VIEW_SCALE_STEP };
+        scale_view_around(&scrolled_window, scale, pointer.get());
+        Propagation::Stop
+    }));
+    scrolled_window.add_controller(scroll);
+
+    let pinch = GestureZoom::new();
+    let initial_scale = Rc::new(Cell::new(1f64));
+    pinch.connect_begin(clone!(@strong initial_scale, @weak scrolled_window => move |_, _| {
+        let ds = gtk_wrappers::get_application(&scrolled_window);
+        initial_scale.set(ds.imp().metafolder.borrow().view_scale);
+    }));
+    pinch.connect_scale_changed(clone!(@strong initial_scale, @strong pointer, @weak scrolled_window => move |gesture, delta| {
+        if !is_visual_zoom_enabled(&scrolled_window) {
+            return;
+        }
+        let center = gesture.bounding_box_center().unwrap_or(pointer.get());
+        scale_view_around(&scrolled_window, initial_scale.get() * delta, center);
+    }));
+    scrolled_window.add_controller(pinch);
+}
+
+// zoom percentages that bring all cells, given as unzoomed (x, y, width, height), inside width x height
+pub(crate) fn fit_zoom(cells: &[(f64, f64, f64, f64)], width: f64, height: f64) -> (i32, i32) {
+    let mut zoom_x = MAX_ZOOM;
+    let mut zoom_y = MAX_ZOOM;
+    for (x, y, w, h) in cells {
+        if *x > 0f64 {
+            zoom_x = zoom_x.min((width - w) * 100f64 / x);
+        }
+        if *y > 0f64 {
+            zoom_y = zoom_y.min((height - h) * 100f64 / y);
+        }
+    }
+    (zoom_x.floor().clamp(MIN_ZOOM, MAX_ZOOM) as i32, zoom_y.floor().clamp(MIN_ZOOM, MAX_ZOOM) as i32)
+}
+
+// keeps the desktop point under (x, y) - in scrolled window coordinates - in place
+fn scale_view_around(scrolled_window: &ScrolledWindow, scale: f64, (x, y): (f64, f64)) {
+    let ds = gtk_wrappers::get_application(scrolled_window);
+    let old_scale = ds.imp().metafolder.borrow().view_scale;
+    let new_scale = scale.clamp(MIN_VIEW_SCALE, MAX_VIEW_SCALE);
+    if new_scale == old_scale {
+        return;
+    }
+    ds.imp().metafolder.borrow_mut().set_view_scale(new_scale, &get_desktop(scrolled_window));
+
+    let factor = new_scale / old_scale;
+    let h = scrolled_window.hadjustment();
+    let v = scrolled_window.vadjustment();
+    let new_h = (h.value() + x) * factor - x;
+    let new_v = (v.value() + y) * factor - y;
+    // the adjustments only grow once the desktop has been measured again
+    glib::idle_add_local_once(clone!(@weak h, @weak v => move || {
+        h.set_value(new_h);
+        v.set_value(new_v);
+    }));
+}
+
+// zoom factors are percentages, 0 meaning not zoomed
+pub(crate) fn zoomed(position: f64, zoom: i32) -> f64 {
+    if zoom == 0 {
+        return position;
+    }
+    position * zoom as f64 / 100f64
 }
 
+// an unzoomed area with its corners zoomed, rounded to whole pixels
+pub(crate) fn zoomed_rect(x: i32, y: i32, width: i32, height: i32, zoom_x: i32, zoom_y: i32) -> (i32, i32, i32, i32) {
+    let left = zoomed(x as f64, zoom_x).round() as i32;
+    let top = zoomed(y as f64, zoom_y).round() as i32;
+    let right = zoomed((x + width) as f64, zoom_x).round() as i32;
+    let bottom = zoomed((y + height) as f64, zoom_y).round() as i32;
+    (left, top, right - left, bottom - top)
+}
+
+pub(crate) fn unzoomed(position: f64, zoom: i32) -> f64 {
+    if zoom == 0 {
+        return position;
+    }
+    position * 100f64 / zoom as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fit_zoom, unzoomed, zoomed};
+
+    #[test]
+    fn zoom_round_trip_restores_exact_positions() {
+        let positions = [(0f64, 0f64), (37f64, 913f64), (1021f64, 3f64), (333f64, 777f64)];
+        let steps = [(50, 50), (130, 70), (200, 200), (90, 110), (170, 60)];
+        for (x, y) in positions {
+            for (zoom_x, zoom_y) in steps {
+                // every view is computed from the unzoomed position, never from the previous view
+                let view = (zoomed(x, zoom_x) as f32, zoomed(y, zoom_y) as f32);
+                assert!((unzoomed(view.0 as f64, zoom_x) - x).abs() < 0.01);
+                assert!((unzoomed(view.1 as f64, zoom_y) - y).abs() < 0.01);
+            }
+            assert_eq!((zoomed(x, 0), zoomed(y, 0)), (x, y));
+            assert_eq!((zoomed(x, 100), zoomed(y, 100)), (x, y));
+        }
+    }
+
+    #[test]
+    fn unzoomed_inverts_zoomed() {
+        for zoom in [50, 75, 100, 120, 150, 200] {
+            for position in [0f64, 1f64, 59f64, 640f64, 1023f64] {
+                let round_trip = unzoomed(zoomed(position, zoom), zoom);
+                assert!((round_trip - position).abs() < 1e-9, "{} at {}% came back as {}", position, zoom, round_trip);
+            }
+        }
+    }
+
+    #[test]
+    fn fit_zoom_brings_all_cells_inside() {
+        let cells = [(0f64, 0f64, 100f64, 100f64), (900f64, 400f64, 100f64, 100f64)];
+        let (zoom_x, zoom_y) = fit_zoom(&cells, 550f64, 300f64);
+        assert_eq!((zoom_x, zoom_y), (50, 50));
+        for (x, y, w, h) in cells {
+            assert!(zoomed(x, zoom_x) + w <= 550f64 && zoomed(y, zoom_y) + h <= 300f64);
+        }
+        // a cell at the origin needs no zoom, so the largest zoom is taken
+        assert_eq!(fit_zoom(&[(0f64, 0f64, 100f64, 100f64)], 800f64, 600f64), (200, 200));
+        assert_eq!(fit_zoom(&[(300f64, 200f64, 100f64, 100f64)], 800f64, 600f64), (200, 200));
+        assert_eq!(fit_zoom(&[(1000f64, 500f64, 100f64, 100f64)], 800f64, 600f64), (70, 100));
+        // never beyond the bounds of the zoom scales
+        assert_eq!(fit_zoom(&[(10000f64, 10000f64, 100f64, 100f64)], 800f64, 600f64), (50, 50));
+    }
+}